@@ -1,11 +1,12 @@
 use crate::{
+    config::Config,
     errors::CrateError,
     profile::{self, Profile},
 };
-use dirs::config_dir;
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
+    io::ErrorKind,
     path::{Path, PathBuf},
 };
 
@@ -17,14 +18,12 @@ struct Active {
     active: bool,
 }
 
-pub fn set_active_profile<P: AsRef<Path>>(path: P) -> Result<(), CrateError> {
+/// Marks a profile as the active profile
+pub fn set_active_profile<P: AsRef<Path>>(config: &Config, path: P) -> Result<(), CrateError> {
     let path = if path.as_ref().is_absolute() {
         path.as_ref().to_owned()
     } else {
-        config_dir()
-            .ok_or_else(|| CrateError::NoConfigDir)?
-            .join("rconfigure/profiles")
-            .join(path)
+        config.profiles_dir().join(path)
     };
 
     let active = Active {
@@ -32,48 +31,68 @@ pub fn set_active_profile<P: AsRef<Path>>(path: P) -> Result<(), CrateError> {
         active: true,
     };
 
-    // FIXME: better error handling
-    fs::write(
-        config_dir()
-            .ok_or_else(|| CrateError::NoConfigDir)?
-            .join("rconfigure/active.toml"),
-        toml::to_string(&active)?,
-    )
-    .unwrap();
+    let file = config.active_file();
+    fs::write(&file, toml::to_string(&active)?)
+        .map_err(|err| CrateError::WriteFailure(file, err))?;
 
     Ok(())
 }
 
-pub fn unset_active_profile() {
-    // TODO: implement unsetting an active profile
-    todo!("unset active profile");
-}
-
-pub fn get_active_profile() -> Result<Option<Profile>, CrateError> {
-    let path = config_dir()
-        .ok_or_else(|| CrateError::NoConfigDir)?
-        .join("rconfigure/active.toml");
+/// Unsets the active profile, restoring the files it overwrote and running its unset hooks,
+/// giving the error that kept the hooks from running if the profile can't be parsed anymore
+pub fn unset_active_profile(config: &Config) -> Result<Option<CrateError>, CrateError> {
+    let path = config.active_file();
 
+    // without an active file no profile has been set, so there is nothing to unset
     let s = match fs::read_to_string(&path) {
         Ok(s) => s,
-        Err(err) => {
-            use std::io::ErrorKind;
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(CrateError::ReadFailure(path, err)),
+    };
+
+    let mut active: Active = toml::from_str(s.as_str())?;
+    let mut skipped = None;
 
-            match err.kind() {
-                ErrorKind::NotFound => {
-                    fs::write(path, "")?;
-                    String::new()
-                }
+    if active.active {
+        match profile::parse(config, &active.profile) {
+            Ok(profile) => profile.unset()?,
 
-                _ => return Err(err.into()),
+            // the files only need what was recorded when the profile was applied, so a profile
+            // whose settings have since been deleted still gets unset without its hooks
+            Err(err) => {
+                profile::restore(config)?;
+                skipped = Some(err);
             }
         }
+    }
+
+    active.active = false;
+    fs::write(&path, toml::to_string(&active)?)
+        .map_err(|err| CrateError::WriteFailure(path, err))?;
+
+    Ok(skipped)
+}
+
+/// Gets the active profile if there is one
+pub fn get_active_profile(config: &Config) -> Result<Option<Profile>, CrateError> {
+    let path = config.active_file();
+
+    let s = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(err) => match err.kind() {
+            ErrorKind::NotFound => {
+                fs::write(&path, "").map_err(|err| CrateError::WriteFailure(path, err))?;
+                String::new()
+            }
+
+            _ => return Err(CrateError::ReadFailure(path, err)),
+        },
     };
 
     let active: Active = toml::from_str(s.as_str())?;
 
     Ok(if active.active {
-        Some(profile::parse(active.profile))
+        Some(profile::parse(config, active.profile)?)
     } else {
         None
     })
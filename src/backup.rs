@@ -0,0 +1,127 @@
+use crate::config::Config;
+use crate::errors::CrateError;
+use crate::transaction::{self, FileWrite};
+use crate::walk::{absolute, walk};
+use chrono::Local;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Gets the original paths of every file stored in a backup
+fn files(config: &Config, id: &str) -> Result<Vec<PathBuf>, CrateError> {
+    let dir = config.backups_dir().join(id);
+    let mut files = Vec::new();
+
+    walk(&dir, &mut files)?;
+    files.sort();
+
+    Ok(files
+        .into_iter()
+        .filter_map(|file| file.strip_prefix(&dir).ok().map(|p| Path::new("/").join(p)))
+        .collect())
+}
+
+/// Copies every existing file that is about to be changed into a new timestamped backup
+pub(crate) fn create(config: &Config, outputs: &[FileWrite]) -> Result<Option<String>, CrateError> {
+    let timestamp = Local::now().format("%Y-%m-%dT%H-%M-%S").to_string();
+    let mut id = timestamp.clone();
+    let mut dir = config.backups_dir().join(&id);
+
+    // applying twice in the same second should not mix two backups together
+    let mut n = 1;
+    while dir.exists() {
+        id = format!("{}.{}", timestamp, n);
+        dir = config.backups_dir().join(&id);
+        n += 1;
+    }
+
+    let mut created = false;
+
+    for output in outputs {
+        // files that don't exist yet or won't change have nothing to lose
+        match fs::read(&output.path) {
+            Ok(current) if current != output.contents => (),
+            _ => continue,
+        }
+
+        let path = absolute(&output.path)?;
+        let stored = dir.join(path.strip_prefix("/").unwrap_or(&path));
+
+        if let Some(parent) = stored.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| CrateError::WriteFailure(parent.to_owned(), err))?;
+        }
+
+        fs::copy(&path, &stored).map_err(|err| CrateError::WriteFailure(stored, err))?;
+        created = true;
+    }
+
+    Ok(if created { Some(id) } else { None })
+}
+
+/// Lists the id of every backup along with the paths of the files it contains, oldest first
+pub fn list(config: &Config) -> Result<Vec<(String, Vec<PathBuf>)>, CrateError> {
+    let dir = config.backups_dir();
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut ids = Vec::new();
+
+    let entries = fs::read_dir(&dir).map_err(|err| CrateError::ReadFailure(dir.to_owned(), err))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|err| CrateError::ReadFailure(dir.to_owned(), err))?;
+
+        if entry.path().is_dir() {
+            ids.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+
+    ids.sort();
+
+    ids.into_iter()
+        .map(|id| {
+            let files = files(config, &id)?;
+            Ok((id, files))
+        })
+        .collect()
+}
+
+/// Restores every file of a backup, or only `path` if it is given
+pub fn restore<P: AsRef<Path>>(
+    config: &Config,
+    id: &str,
+    path: Option<P>,
+) -> Result<(), CrateError> {
+    let dir = config.backups_dir().join(id);
+
+    if !dir.is_dir() {
+        return Err(CrateError::NoSuchBackup(id.to_owned()));
+    }
+
+    let path = path.map(absolute).transpose()?;
+    let mut outputs = Vec::new();
+
+    for file in files(config, id)? {
+        if let Some(path) = &path {
+            if file != *path {
+                continue;
+            }
+        }
+
+        let stored = dir.join(file.strip_prefix("/").unwrap_or(&file));
+        let contents = fs::read(&stored).map_err(|err| CrateError::ReadFailure(stored, err))?;
+        outputs.push(FileWrite::new(file, contents));
+    }
+
+    if outputs.is_empty() {
+        if let Some(path) = path {
+            return Err(CrateError::NotInBackup(id.to_owned(), path));
+        }
+    }
+
+    transaction::write_all(&outputs)
+}
@@ -0,0 +1,128 @@
+use crate::errors::CrateError;
+use crate::transaction::{self, FileWrite};
+use std::{fs, io::ErrorKind, path::Path};
+
+/// Gets the setting a `BEGIN rconfigure` or `END rconfigure` marker line belongs to, which is
+/// only a marker when the whole line is a comment starting with it
+fn marker<'a>(line: &'a str, kind: &str, comment: &str) -> Option<&'a str> {
+    let id = line
+        .trim()
+        .strip_prefix(comment)?
+        .trim_start()
+        .strip_prefix(kind)?
+        .strip_prefix(" rconfigure ")?;
+
+    Some(id.trim())
+}
+
+/// Finds the first line and the last line of the first complete managed block of a setting, or of
+/// any setting if none is given
+fn find(lines: &[&str], id: Option<&str>, comment: &str) -> Option<(usize, usize)> {
+    let begin = lines
+        .iter()
+        .position(|line| match (marker(line, "BEGIN", comment), id) {
+            (Some(block), Some(id)) => id == block,
+            (Some(_), None) => true,
+            (None, _) => false,
+        })?;
+
+    let id = marker(lines[begin], "BEGIN", comment);
+    let end = lines[begin..]
+        .iter()
+        .position(|line| marker(line, "END", comment) == id)?;
+
+    Some((begin, begin + end))
+}
+
+/// Puts the contents of a setting between its markers, replacing the block if the file already
+/// has one or appending it otherwise
+pub fn insert(current: &str, id: &str, comment: &str, contents: &str, newline: &str) -> String {
+    let lines: Vec<&str> = current.lines().collect();
+
+    let mut block = format!("{} BEGIN rconfigure {}{}", comment, id, newline);
+    block.push_str(contents);
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        block.push_str(newline);
+    }
+    block.push_str(&format!("{} END rconfigure {}{}", comment, id, newline));
+
+    let mut output = String::new();
+
+    match find(&lines, Some(id), comment) {
+        Some((begin, end)) => {
+            for line in &lines[..begin] {
+                output.push_str(line);
+                output.push_str(newline);
+            }
+
+            output.push_str(&block);
+
+            for line in &lines[end + 1..] {
+                output.push_str(line);
+                output.push_str(newline);
+            }
+        }
+
+        None => {
+            output.push_str(current);
+
+            if !current.is_empty() && !current.ends_with('\n') {
+                output.push_str(newline);
+            }
+
+            output.push_str(&block);
+        }
+    }
+
+    output
+}
+
+/// Removes the managed block of a setting, or every managed block if no setting is given
+pub fn remove(current: &str, id: Option<&str>, comment: &str) -> String {
+    let newline = if current.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut lines: Vec<&str> = current.lines().collect();
+
+    // a block missing its end marker is left alone rather than eating the rest of the file
+    while let Some((begin, end)) = find(&lines, id, comment) {
+        lines.drain(begin..=end);
+    }
+
+    let mut output = lines.join(newline);
+
+    if !lines.is_empty() && current.ends_with('\n') {
+        output.push_str(newline);
+    }
+
+    output
+}
+
+/// Get the contents of a file on disk without its managed blocks, `None` if it is missing or has
+/// none of them
+pub fn removed_from_file(
+    path: &Path,
+    id: Option<&str>,
+    comment: &str,
+) -> Result<Option<String>, CrateError> {
+    let current = match fs::read_to_string(path) {
+        Ok(current) => current,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(CrateError::ReadFailure(path.to_owned(), err)),
+    };
+
+    let contents = remove(&current, id, comment);
+
+    Ok(Some(contents).filter(|contents| *contents != current))
+}
+
+/// Removes managed blocks from a file on disk, leaving the rest of it as is
+pub fn remove_from_file(path: &Path, id: Option<&str>, comment: &str) -> Result<(), CrateError> {
+    if let Some(contents) = removed_from_file(path, id, comment)? {
+        transaction::write_all(&[FileWrite::new(path, contents.into_bytes())])?;
+    }
+
+    Ok(())
+}
@@ -35,7 +35,7 @@ where
         where
             E: Error,
         {
-            S::from_str(&*value).map(Some).map_err(Error::custom)
+            S::from_str(&value).map(Some).map_err(Error::custom)
         }
 
         fn visit_bool<E>(self, value: bool) -> Result<Self::Value, E>
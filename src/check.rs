@@ -0,0 +1,378 @@
+use crate::config::Config;
+use crate::errors::CrateError;
+use crate::profile::{self, Profile};
+use crate::setting::{self, Setting, TargetValue};
+use crate::{template, walk};
+use rhai::{Engine, EvalAltResult};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Something wrong in the config directory that would otherwise only show up while applying a
+/// profile, or not at all
+#[derive(Error, Debug)]
+pub enum Problem {
+    #[error(transparent)]
+    Invalid(#[from] CrateError),
+
+    #[error("the profile {profile:?} lists the setting {setting:?} which does not exist")]
+    MissingSetting { profile: PathBuf, setting: PathBuf },
+
+    #[error("the setting {setting:?} targets the template {target:?} which does not exist")]
+    MissingTemplate { setting: PathBuf, target: PathBuf },
+
+    #[error("the template {template:?} uses {variable:?} which no setting provides")]
+    UnprovidedVariable { template: PathBuf, variable: String },
+
+    #[error("the setting {setting:?} sets {key:?} in [{table}] which no template uses")]
+    UnusedKey {
+        setting: PathBuf,
+        table: String,
+        key: String,
+    },
+
+    #[error(
+        "the profile {profile:?} sets {key:?} for the setting {setting:?} which no template uses"
+    )]
+    UnusedParam {
+        profile: PathBuf,
+        setting: PathBuf,
+        key: String,
+    },
+
+    #[error("the script {0:?} does not compile")]
+    ScriptError(PathBuf, #[source] Box<EvalAltResult>),
+
+    #[error("{path:?} has the unknown table [{table}]")]
+    UnknownTable { path: PathBuf, table: String },
+
+    #[error("{path:?} has the unknown field {field:?} in [{table}]")]
+    UnknownField {
+        path: PathBuf,
+        table: String,
+        field: String,
+    },
+
+    #[error("settings {setting1:?} and {setting2:?} of the profile {profile:?} both set {key:?} in target {target:?}")]
+    SettingConflict {
+        profile: PathBuf,
+        setting1: String,
+        setting2: String,
+        target: PathBuf,
+        key: String,
+    },
+}
+
+/// Reads a file as a plain toml table, leaving reporting errors to the actual parsing
+fn read_table(path: &Path) -> Option<toml::value::Table> {
+    let s = fs::read_to_string(path).ok()?;
+
+    match toml::from_str(&s).ok()? {
+        toml::Value::Table(table) => Some(table),
+        _ => None,
+    }
+}
+
+/// Reports the fields of a table that aren't one of the known ones
+fn unknown_fields(
+    path: &Path,
+    name: &str,
+    table: Option<&toml::Value>,
+    fields: &[&str],
+    problems: &mut Vec<Problem>,
+) {
+    for field in table
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(|t| t.keys())
+    {
+        if !fields.contains(&field.as_str()) {
+            problems.push(Problem::UnknownField {
+                path: path.to_owned(),
+                table: name.to_owned(),
+                field: field.to_owned(),
+            });
+        }
+    }
+}
+
+/// Checks the fields of a profile and the settings it lists, returning the paths of the listed
+/// settings that exist
+fn check_profile_file(config: &Config, path: &Path, problems: &mut Vec<Problem>) -> Vec<PathBuf> {
+    let table = match read_table(path) {
+        Some(table) => table,
+        None => return Vec::new(),
+    };
+
+    for name in table.keys().filter(|name| *name != "profile") {
+        problems.push(Problem::UnknownTable {
+            path: path.to_owned(),
+            table: name.to_owned(),
+        });
+    }
+
+    let profile = table.get("profile");
+    unknown_fields(path, "profile", profile, profile::FIELDS, problems);
+
+    let entries = |field| {
+        profile
+            .and_then(|p| p.get(field))
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                entry
+                    .as_str()
+                    .or_else(|| entry.get("setting").and_then(toml::Value::as_str))
+            })
+    };
+
+    let mut settings = Vec::new();
+
+    for entry in entries("settings").chain(entries("remove_settings")) {
+        let setting = setting::resolve(config, Path::new(entry));
+
+        if setting.is_file() {
+            settings.push(setting);
+        } else {
+            problems.push(Problem::MissingSetting {
+                profile: path.to_owned(),
+                setting,
+            });
+        }
+    }
+
+    settings
+}
+
+/// Checks whether a template uses a key, where templates that exist but couldn't be read are
+/// assumed to use everything
+fn uses(template: &Path, key: &str, variables: &HashMap<PathBuf, HashSet<String>>) -> bool {
+    template.is_file()
+        && match variables.get(template) {
+            Some(vars) => vars.contains(key),
+            None => true,
+        }
+}
+
+/// Checks which of the templates a table of values applies to use each of its keys
+fn unused_keys(
+    setting: &Setting,
+    name: String,
+    table: &HashMap<String, TargetValue>,
+    templates: &[PathBuf],
+    variables: &HashMap<PathBuf, HashSet<String>>,
+    problems: &mut Vec<Problem>,
+) {
+    let mut keys: Vec<&String> = table
+        .iter()
+        // the keys a script produces aren't known until it runs
+        .filter(|(_, v)| !matches!(v, TargetValue::Script { .. }))
+        .map(|(k, _)| k)
+        .collect();
+    keys.sort();
+
+    for key in keys {
+        let used = templates.iter().any(|t| uses(t, key, variables));
+
+        if !used {
+            problems.push(Problem::UnusedKey {
+                setting: setting.path(),
+                table: name.to_owned(),
+                key: key.to_owned(),
+            });
+        }
+    }
+}
+
+/// Reports the conflicts of a profile that are errors or where the order of its settings
+/// decides which value wins, other ones are overridden on purpose through their priority
+fn conflicts(profile: &Profile, problems: &mut Vec<Problem>) {
+    for (setting1, setting2, target, key) in profile.conflicts(None) {
+        if profile.strict() || setting1.priority() == setting2.priority() {
+            problems.push(Problem::SettingConflict {
+                profile: profile.path(),
+                setting1: setting1.name(),
+                setting2: setting2.name(),
+                target,
+                key,
+            });
+        }
+    }
+}
+
+/// Checks every profile, setting, template and script in the config directory without writing
+/// anything, returning everything that is wrong with them
+pub fn check(config: &Config, engine: &Engine) -> Result<Vec<Problem>, CrateError> {
+    let mut problems = Vec::new();
+    let mut profiles = Vec::new();
+    let mut setting_paths = setting::list(config)?;
+
+    for path in profile::list(config)? {
+        setting_paths.extend(check_profile_file(config, &path, &mut problems));
+
+        match profile::parse(config, &path) {
+            Ok(profile) => profiles.push(profile),
+            // broken settings are reported when checking the setting itself
+            Err(CrateError::SettingError(_)) => (),
+            Err(err) => problems.push(err.into()),
+        }
+    }
+
+    setting_paths.sort();
+    setting_paths.dedup();
+
+    let mut settings = Vec::new();
+
+    for path in setting_paths {
+        if let Some(table) = read_table(&path) {
+            unknown_fields(
+                &path,
+                "setting",
+                table.get("setting"),
+                setting::FIELDS,
+                &mut problems,
+            );
+        }
+
+        match setting::parse(config, &path) {
+            Ok(setting) => settings.push(setting),
+            Err(err) => problems.push(err.into()),
+        }
+    }
+
+    // templates outside of the templates directory are checked too when a setting targets them
+    let mut template_paths = template::list(config)?;
+    template_paths.extend(
+        settings
+            .iter()
+            .flat_map(Setting::targets)
+            .filter(|t| t.is_file()),
+    );
+    template_paths.sort();
+    template_paths.dedup();
+
+    let mut variables = HashMap::new();
+    let mut defaults = HashMap::new();
+
+    for path in template_paths {
+        match template::variables(&path) {
+            Ok((header, vars)) => {
+                defaults.insert(path.to_owned(), header.variables().to_owned());
+                variables.insert(path, vars);
+            }
+
+            Err(err) => problems.push(err.into()),
+        }
+    }
+
+    let mut provided: HashMap<PathBuf, HashSet<String>> = HashMap::new();
+
+    for setting in &settings {
+        for target in setting.targets() {
+            // directory tables only provide values to the templates inside them
+            if target.is_dir() {
+                continue;
+            }
+
+            if !target.is_file() {
+                problems.push(Problem::MissingTemplate {
+                    setting: setting.path(),
+                    target,
+                });
+
+                continue;
+            }
+
+            match setting.values(setting.compose_map(&target), engine) {
+                Ok(map) => provided.entry(target).or_default().extend(map.into_keys()),
+                Err(err) => problems.push(err.into()),
+            }
+        }
+
+        let targets = setting.templates();
+
+        for (path, table) in setting.target_tables() {
+            let templates: Vec<PathBuf> = targets
+                .iter()
+                .filter(|t| t.starts_with(path))
+                .cloned()
+                .collect();
+            let name = path
+                .strip_prefix(config.templates_dir())
+                .unwrap_or(path)
+                .display()
+                .to_string();
+
+            unused_keys(setting, name, table, &templates, &variables, &mut problems);
+        }
+
+        if let Some(table) = setting.global_target() {
+            let name = String::from("global");
+            unused_keys(setting, name, table, &targets, &variables, &mut problems);
+        }
+    }
+
+    let mut seen = HashSet::new();
+
+    for profile in &profiles {
+        for setting in profile.settings() {
+            let mut keys: Vec<&String> = setting.params().keys().collect();
+            keys.sort();
+
+            for key in keys {
+                for target in setting.templates() {
+                    provided.entry(target).or_default().insert(key.to_owned());
+                }
+
+                // inherited settings carry the values of the profile listing them
+                if !seen.insert((setting.path(), key.to_owned())) {
+                    continue;
+                }
+
+                let used = setting.templates().iter().any(|t| uses(t, key, &variables));
+
+                if !used {
+                    problems.push(Problem::UnusedParam {
+                        profile: profile.path(),
+                        setting: setting.path(),
+                        key: key.to_owned(),
+                    });
+                }
+            }
+        }
+
+        conflicts(profile, &mut problems);
+    }
+
+    let mut templates: Vec<&PathBuf> = variables.keys().collect();
+    templates.sort();
+
+    for template in templates {
+        let mut vars: Vec<&String> = variables[template].iter().collect();
+        vars.sort();
+
+        for variable in vars {
+            let is_provided = provided
+                .get(template)
+                .is_some_and(|keys| keys.contains(variable))
+                || defaults[template].contains_key(variable);
+
+            if !is_provided {
+                problems.push(Problem::UnprovidedVariable {
+                    template: template.to_owned(),
+                    variable: variable.to_owned(),
+                });
+            }
+        }
+    }
+
+    for path in walk::list(&config.script_dir())? {
+        if let Err(err) = engine.compile_file(path.to_owned()) {
+            problems.push(Problem::ScriptError(path, err));
+        }
+    }
+
+    Ok(problems)
+}
@@ -2,6 +2,20 @@ use clap::{Arg, SubCommand, ArgMatches};
 
 pub fn matches() -> ArgMatches<'static> {
     app_from_crate!()
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help("Prints what would be written without writing anything or running hooks"),
+        )
+        .arg(
+            Arg::with_name("config-dir")
+                .long("config-dir")
+                .env("RCONFIGURE_HOME")
+                .takes_value(true)
+                .global(true)
+                .help("Reads profiles, settings and templates from this directory"),
+        )
         .subcommand(
             SubCommand::with_name("profile")
                 .alias("p")
@@ -16,6 +30,11 @@ pub fn matches() -> ArgMatches<'static> {
                     SubCommand::with_name("unset")
                         .alias("u")
                         .about("Unsets the active profile (runs unset hooks)"),
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .alias("l")
+                        .about("Lists every profile"),
                 ),
         )
         .subcommand(
@@ -49,8 +68,83 @@ pub fn matches() -> ArgMatches<'static> {
                                 .multiple(true)
                                 .takes_value(true),
                         ),
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .alias("l")
+                        .about("Lists every setting")
+                        .arg(
+                            Arg::with_name("enabled")
+                                .long("enabled")
+                                .conflicts_with("available")
+                                .help("Only lists the settings enabled in the active profile"),
+                        )
+                        .arg(
+                            Arg::with_name("available")
+                                .long("available")
+                                .help("Only lists the settings not enabled in the active profile"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("template")
+                .alias("t")
+                .about("Inspect templates")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .alias("l")
+                        .about("Lists every template and where it is written"),
                 ),
         )
         .subcommand(SubCommand::with_name("reload").about("Reloads the active profile"))
+        .subcommand(
+            SubCommand::with_name("status")
+                .about("Shows the active profile and whether its files changed since the last apply"),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .alias("b")
+                .about("Manage backups of overwritten files")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .alias("l")
+                        .about("Lists every backup and the files it contains"),
+                )
+                .subcommand(
+                    SubCommand::with_name("restore")
+                        .alias("r")
+                        .about("Restores the files of a backup")
+                        .arg(Arg::with_name("ID").index(1).required(true))
+                        .arg(
+                            Arg::with_name("PATH")
+                                .index(2)
+                                .help("Only restore this file from the backup"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Shows the changes applying a profile would make (defaults to the active profile)")
+                .arg(Arg::with_name("PROFILE").index(1)),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Shows the value of every variable of a template in the active profile and where it came from")
+                .arg(Arg::with_name("TEMPLATE").index(1).required(true))
+                .arg(
+                    Arg::with_name("KEY")
+                        .index(2)
+                        .help("Only explain this variable"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks every profile, setting, template and script for problems without writing anything"),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Shows a profile, setting or template")
+                .arg(Arg::with_name("ITEM").index(1).required(true)),
+        )
         .get_matches()
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use crate::errors::CrateError;
+use dirs::config_dir;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Default)]
+struct ConfigDeserialized {
+    #[serde(default)]
+    directories: DirectoriesTable,
+}
+
+/// Overrides for the locations of the config subdirectories, relative to the root
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DirectoriesTable {
+    profiles: Option<PathBuf>,
+    settings: Option<PathBuf>,
+    templates: Option<PathBuf>,
+    script: Option<PathBuf>,
+}
+
+/// The root directory rconfigure reads profiles, settings, templates and scripts from
+#[derive(Clone, Debug)]
+pub struct Config {
+    root: PathBuf,
+    profiles: PathBuf,
+    settings: PathBuf,
+    templates: PathBuf,
+    script: PathBuf,
+}
+
+impl Config {
+    /// Creates a config rooted at an arbitrary directory using the default subdirectories
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        let root = root.into();
+
+        Config {
+            profiles: root.join("profiles"),
+            settings: root.join("settings"),
+            templates: root.join("templates"),
+            script: root.join("script"),
+            root,
+        }
+    }
+
+    /// Creates a config rooted at a directory, relocating subdirectories as declared in its
+    /// `rconfigure.toml` if there is one
+    pub fn load<P: Into<PathBuf>>(root: P) -> Result<Self, CrateError> {
+        let mut config = Config::new(root);
+        let path = config.root.join("rconfigure.toml");
+
+        let s = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(config),
+            Err(err) => return Err(CrateError::ErrorReadingConfig(path, err)),
+        };
+
+        let table: ConfigDeserialized = toml::from_str(s.as_str())
+            .map_err(|err| CrateError::ErrorParsingConfig(path.to_owned(), err))?;
+
+        let directories = table.directories;
+
+        if let Some(dir) = directories.profiles {
+            config.profiles = config.root.join(dir);
+        }
+
+        if let Some(dir) = directories.settings {
+            config.settings = config.root.join(dir);
+        }
+
+        if let Some(dir) = directories.templates {
+            config.templates = config.root.join(dir);
+        }
+
+        if let Some(dir) = directories.script {
+            config.script = config.root.join(dir);
+        }
+
+        Ok(config)
+    }
+
+    /// Creates a config rooted at `rconfigure` inside the user's config directory
+    pub fn from_config_dir() -> Result<Self, CrateError> {
+        Config::load(
+            config_dir()
+                .ok_or(CrateError::NoConfigDir)?
+                .join("rconfigure"),
+        )
+    }
+
+    /// Get the root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the directory containing the profiles
+    pub fn profiles_dir(&self) -> PathBuf {
+        self.profiles.to_owned()
+    }
+
+    /// Get the directory containing the settings
+    pub fn settings_dir(&self) -> PathBuf {
+        self.settings.to_owned()
+    }
+
+    /// Get the directory containing the templates
+    pub fn templates_dir(&self) -> PathBuf {
+        self.templates.to_owned()
+    }
+
+    /// Get the directory containing the rhai scripts
+    pub fn script_dir(&self) -> PathBuf {
+        self.script.to_owned()
+    }
+
+    /// Get the path of the file storing the active profile
+    pub fn active_file(&self) -> PathBuf {
+        self.root.join("active.toml")
+    }
+
+    /// Get the path of the record of files written by the last apply
+    pub fn state_file(&self) -> PathBuf {
+        self.root.join("state.toml")
+    }
+
+    /// Get the directory containing the timestamped backups
+    pub fn backups_dir(&self) -> PathBuf {
+        self.root.join("backups")
+    }
+
+    /// Get the directory containing the contents of files from before rconfigure touched them
+    pub fn originals_dir(&self) -> PathBuf {
+        self.root.join("originals")
+    }
+
+    /// Get the path of the index of original files
+    pub fn originals_file(&self) -> PathBuf {
+        self.root.join("originals.toml")
+    }
+}
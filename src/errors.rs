@@ -1,36 +1,151 @@
 use std::path::PathBuf;
 
 use thiserror::Error;
-use crate::setting::Setting;
 
 #[derive(Error, Debug)]
 pub enum ProfileError {
-    #[error("setting target conflict")]
+    #[error("settings {setting1:?} and {setting2:?} both set {key:?} in target {target:?}")]
     SettingConflict {
-        setting1: Setting,
-        setting2: Setting,
+        setting1: String,
+        setting2: String,
+        target: PathBuf,
+        key: String,
     },
 
+    #[error("the profile {0:?} does not exist")]
+    MissingProfile(PathBuf),
+
+    #[error("the profile {0:?} extends itself through {1:?}")]
+    InheritanceCycle(PathBuf, Vec<PathBuf>),
+
+    #[error("the setting {0:?} is not enabled")]
+    SettingNotEnabled(PathBuf),
+
     #[error("there was an error reading the profile {0:?}")]
-    ErrorReadingProfile(PathBuf, std::io::Error),
+    ErrorReadingProfile(PathBuf, #[source] std::io::Error),
+
+    #[error("there was an error parsing the profile {0:?}")]
+    ErrorParsingProfile(PathBuf, #[source] toml::de::Error),
+
+    #[error("there was an error editing the profile {0:?}")]
+    ErrorEditingProfile(PathBuf, #[source] toml_edit::TomlError),
+
+    #[error("expected {key:?} in the profile {path:?} to be {expected}")]
+    UnexpectedType {
+        path: PathBuf,
+        key: String,
+        expected: &'static str,
+    },
 
     #[error("there was an error writing the profile {0:?}")]
-    ErrorWritingProfile(PathBuf, std::io::Error),
+    ErrorWritingProfile(PathBuf, #[source] std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum SettingError {
+    #[error("the setting {0:?} does not exist")]
+    MissingSetting(PathBuf),
+
+    #[error("there was an error reading the setting {0:?}")]
+    ErrorReadingSetting(PathBuf, #[source] std::io::Error),
+
+    #[error("there was an error parsing the setting {0:?}")]
+    ErrorParsingSetting(PathBuf, #[source] toml::de::Error),
+
+    #[error("the setting {0:?} extends itself through {1:?}")]
+    InheritanceCycle(PathBuf, Vec<PathBuf>),
+
+    #[error(
+        "the setting {0:?} merges into {1:?} without a format, and its extension is not json, jsonc, yaml, yml or toml"
+    )]
+    UnknownFormat(PathBuf, PathBuf),
+
+    #[error("the setting {0:?} merges into the relative path {1:?}")]
+    RelativeMergeTarget(PathBuf, PathBuf),
+
+    #[error("the rhai script {0:?} failed")]
+    ScriptFailure(PathBuf, #[source] Box<rhai::EvalAltResult>),
+}
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("the template {0:?} does not exist")]
+    MissingTemplate(PathBuf),
+
+    #[error("there was an error reading the template {0:?}")]
+    ErrorReadingTemplate(PathBuf, #[source] std::io::Error),
+
+    #[error("the template {0:?} is missing its header")]
+    MissingTemplateHeader(PathBuf),
+
+    #[error("the front matter of the template {0:?} is missing its closing `+++`")]
+    UnterminatedFrontMatter(PathBuf),
+
+    #[error("there was an error parsing the front matter of the template {0:?}")]
+    ErrorParsingFrontMatter(PathBuf, #[source] toml::de::Error),
+
+    #[error("failed to render the template {0:?}")]
+    RenderFailure(PathBuf, #[source] minijinja::Error),
 
-    // #[error("there was an error serializing the profile {0:?}")]
-    // ErrorSerializingProfile(PathBuf, toml::ser::Error),
+    #[error("failed to expand the output path of the template {0:?}")]
+    PathExpansionFailure(PathBuf, #[source] minijinja::Error),
+
+    #[error("the output path in {file:?} uses the undefined variable ${variable}")]
+    UndefinedVariable { file: PathBuf, variable: String },
+}
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("there was an error parsing the structured file {0:?}")]
+    ErrorParsingTarget(PathBuf, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("there was an error serializing the structured file {0:?}")]
+    ErrorSerializingTarget(PathBuf, #[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("the structured file {0:?} is not a table at the top level")]
+    NotATable(PathBuf),
+
+    #[error("the structured file {0:?} can't hold a null value")]
+    NullValue(PathBuf),
+
+    #[error("the yaml file {0:?} is written in a way merging into it does not support")]
+    UnsupportedFormatting(PathBuf),
 }
 
 #[derive(Error, Debug)]
-pub enum ActiveError {}
+pub enum ActiveError {
+    #[error("there is no active profile")]
+    NoActiveProfile,
+}
 
 #[derive(Error, Debug)]
 pub enum CrateError {
-    #[error("error in profile module")]
+    #[error(transparent)]
     ProfileError(#[from] ProfileError),
 
-    #[error("there was a file system error")]
-    FileSystemError(#[from] std::io::Error),
+    #[error(transparent)]
+    SettingError(#[from] SettingError),
+
+    #[error(transparent)]
+    TemplateError(#[from] TemplateError),
+
+    #[error(transparent)]
+    MergeError(#[from] MergeError),
+
+    #[error(transparent)]
+    ActiveError(#[from] ActiveError),
+
+    #[error("failed to talk to the terminal")]
+    TerminalFailure(#[source] std::io::Error),
+
+    #[error("failed to read {0:?}")]
+    ReadFailure(PathBuf, #[source] std::io::Error),
+
+    #[error("failed to write {0:?}")]
+    WriteFailure(PathBuf, #[source] std::io::Error),
+
+    #[error("failed to put back {:?} after a write failed", .1.iter().map(|(path, _)| path).collect::<Vec<_>>())]
+    RollbackFailure(#[source] Box<CrateError>, Vec<(PathBuf, std::io::Error)>),
 
     #[error("there was an error serializing a profile")]
     TomlSerializationError(#[from] toml::ser::Error),
@@ -40,4 +155,25 @@ pub enum CrateError {
 
     #[error("could not find config directory")]
     NoConfigDir,
-}
\ No newline at end of file
+
+    #[error("there was an error reading the config {0:?}")]
+    ErrorReadingConfig(PathBuf, #[source] std::io::Error),
+
+    #[error("there was an error parsing the config {0:?}")]
+    ErrorParsingConfig(PathBuf, #[source] toml::de::Error),
+
+    #[error("an answer is needed but stdin is not a terminal")]
+    NotInteractive,
+
+    #[error("there is no backup {0:?}")]
+    NoSuchBackup(String),
+
+    #[error("backup {0:?} does not contain {1:?}")]
+    NotInBackup(String, PathBuf),
+
+    #[error("found {0} problem(s) in the config directory")]
+    CheckFailed(usize),
+
+    #[error("{0:?} is not a profile, setting or template")]
+    NoSuchItem(PathBuf),
+}
@@ -1,6 +1,7 @@
 use crate::bool_false_as_none;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::process::{Command, Output};
@@ -16,7 +17,6 @@ impl Deref for StringOrFalseAsNone {
     }
 }
 
-// TODO: make hooks that run on unset
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Hook {
     cmd: String,
@@ -27,6 +27,22 @@ pub struct Hook {
     env: HashMap<String, StringOrFalseAsNone>,
 }
 
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cmd)?;
+
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+
+        if let Some(dir) = &self.cwd {
+            write!(f, " (in {})", dir.display())?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Hook {
     pub fn run(&self) -> Option<Output> {
         let mut cmd = Command::new(&self.cmd);
@@ -0,0 +1,33 @@
+//! Tight-knit, profile-based control over any config files on your system.
+//!
+//! Profiles group settings together, settings contain values to use in the templates, and
+//! templates contain the file contents and the path to overwrite. Everything is read relative to
+//! a [`Config`] root, which defaults to `rconfigure` inside the user's config directory and can
+//! relocate each of its subdirectories through an `rconfigure.toml`.
+
+mod block;
+mod bool_false_as_none;
+mod originals;
+mod transaction;
+mod walk;
+
+pub mod active;
+pub mod backup;
+pub mod check;
+pub mod config;
+pub mod errors;
+pub mod hook;
+pub mod merge;
+pub mod profile;
+pub mod script;
+pub mod setting;
+pub mod state;
+pub mod template;
+
+pub use config::Config;
+pub use errors::CrateError;
+pub use hook::Hook;
+pub use profile::Profile;
+pub use script::ScriptValue;
+pub use setting::{Setting, TargetValue};
+pub use template::generate_config;
@@ -1,97 +1,520 @@
 #[macro_use]
 extern crate clap;
 
-use errors::CrateError;
+use ansi_term::{Colour, Style};
+use pick::Item;
+use rconfigure::errors::ActiveError;
+use rconfigure::profile::{Enabled, FileChange, FileDiff};
+use rconfigure::setting::Origin;
+use rconfigure::state::State;
+use rconfigure::{active, backup, check, profile, setting, template, Config, CrateError, Setting};
 use rhai::Engine;
+use similar::{ChangeTag, TextDiff};
+use std::error::Error;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
-mod active;
-mod bool_false_as_none;
 mod cli;
-mod errors;
-mod hook;
-mod profile;
-mod script;
-mod setting;
-mod template;
-
-fn main() -> Result<(), CrateError> {
+mod pick;
+mod show;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        print_causes(&err);
+
+        if let CrateError::RollbackFailure(_, failures) = &err {
+            for (path, err) in failures {
+                eprintln!("  failed to put back {:?}: {}", path, err);
+            }
+        }
+
+        std::process::exit(1);
+    }
+}
+
+/// Prints every underlying cause of an error
+fn print_causes(err: &dyn Error) {
+    let mut source = err.source();
+    while let Some(err) = source {
+        eprintln!("  caused by: {}", err);
+        source = err.source();
+    }
+}
+
+/// Reports an entry of a list that couldn't be shown without stopping the rest of the list
+fn list_entry(path: &Path, result: Result<(), CrateError>) {
+    if let Err(err) = result {
+        eprintln!("{}: {}", path.display(), err);
+    }
+}
+
+/// Prints what a dry run would do to each file
+fn print_dry_run(name: &str, changes: Vec<(PathBuf, FileChange)>) {
+    println!("dry run of profile {:?}, nothing will be written", name);
+
+    for (path, change) in changes {
+        println!("{:?} would be {}", path, change);
+    }
+}
+
+/// Prints a unified diff of a file, coloured when it is going to a terminal
+fn print_diff(diff: &FileDiff) {
+    let colour = std::io::stdout().is_terminal();
+    let paint = |style: Style, s: String| {
+        if colour {
+            style.paint(s).to_string()
+        } else {
+            s
+        }
+    };
+
+    let old_header = match diff.current {
+        Some(_) => diff.path.display().to_string(),
+        None => String::from("/dev/null"),
+    };
+    let current = diff.current.as_deref().unwrap_or_default();
+    let text_diff = TextDiff::from_lines(current, &diff.contents);
+
+    println!(
+        "{}",
+        paint(Style::new().bold(), format!("--- {}", old_header))
+    );
+    println!(
+        "{}",
+        paint(Style::new().bold(), format!("+++ {}", diff.path.display()))
+    );
+
+    for hunk in text_diff.unified_diff().iter_hunks() {
+        println!(
+            "{}",
+            paint(Colour::Cyan.normal(), hunk.header().to_string())
+        );
+
+        for change in hunk.iter_changes() {
+            let (sign, style) = match change.tag() {
+                ChangeTag::Delete => ("-", Colour::Red.normal()),
+                ChangeTag::Insert => ("+", Colour::Green.normal()),
+                ChangeTag::Equal => (" ", Style::new()),
+            };
+
+            let line = format!("{}{}", sign, change.value().trim_end_matches('\n'));
+            println!("{}", paint(style, line));
+
+            if change.missing_newline() {
+                println!("\\ No newline at end of file");
+            }
+        }
+    }
+}
+
+/// Asks whether an enabled setting conflicting with a new one in a strict profile gets replaced
+fn confirm_replace(
+    replaced: &Setting,
+    setting: &Setting,
+    target: &Path,
+    key: &str,
+) -> Result<bool, CrateError> {
+    println!("failed to apply profile, found setting conflict!");
+
+    // the prompt can't be answered without a terminal, --noconfirm replaces without asking
+    if !std::io::stdin().is_terminal() {
+        return Err(CrateError::NotInteractive);
+    }
+
+    let prompt = format!(
+        "settings {:?} and {:?} both set {:?} in target {:?}, replace setting {:?} with {:?}?",
+        replaced.name(),
+        setting.name(),
+        key,
+        target,
+        replaced.name(),
+        setting.name()
+    );
+
+    Ok(quiz::confirm(&prompt))
+}
+
+fn run() -> Result<(), CrateError> {
     let engine = Engine::new();
     let matches = cli::matches();
+    let config = match matches.value_of("config-dir") {
+        Some(dir) => Config::load(dir)?,
+        None => Config::from_config_dir()?,
+    };
+    let dry_run = matches.is_present("dry-run");
 
     match matches.subcommand() {
-        ("profile", Some(sub_matches)) => {
-            match sub_matches.subcommand() {
-                ("set", Some(sub_matches)) => {
-                    if let Some(profile) = sub_matches.value_of("PROFILE") {
-                        active::set_active_profile(profile)?;
-                        profile::parse(profile).apply(&engine);
+        ("profile", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("set", Some(sub_matches)) => {
+                let path = match sub_matches.value_of("PROFILE") {
+                    Some(path) => PathBuf::from(path),
+                    None => {
+                        let items = profile::list(&config)?
+                            .into_iter()
+                            .map(|path| Item {
+                                name: profile::parse(&config, &path)
+                                    .map(|p| p.name())
+                                    .unwrap_or_default(),
+                                path,
+                            })
+                            .collect();
 
-                        Ok(())
-                    } else {
-                        todo!("use quiz crate");
-                        // TODO: use quiz
+                        match pick::one("pick a profile", items)? {
+                            Some(path) => path,
+                            None => return Ok(()),
+                        }
                     }
+                };
+
+                let profile = profile::parse(&config, &path)?;
+
+                if dry_run {
+                    print_dry_run(&profile.name(), profile.dry_run(&engine)?);
+                    Ok(())
+                } else {
+                    profile.apply(&engine)?;
+                    active::set_active_profile(&config, path)
                 }
+            }
 
-                ("unset", Some(_sub_matches)) => Ok(active::unset_active_profile()),
+            ("unset", Some(_sub_matches)) => {
+                if let Some(err) = active::unset_active_profile(&config)? {
+                    eprintln!("warning: skipped the unset hooks: {}", err);
+                    print_causes(&err);
+                }
 
-                _ => Ok(()),
+                Ok(())
             }
-        }
+
+            ("list", Some(_sub_matches)) => {
+                for path in profile::list(&config)? {
+                    list_entry(&path, show::profile(&config, &path));
+                }
+
+                Ok(())
+            }
+
+            _ => Ok(()),
+        },
 
         ("setting", Some(sub_matches)) => {
             match sub_matches.subcommand() {
                 ("enable", Some(sub_matches)) => {
-                    if let Ok(Some(mut profile)) = active::get_active_profile() {
-                        if let Some(settings) = sub_matches.values_of("SETTINGS") {
-                            for setting in settings {
-                                profile
-                                    .enable_setting(setting, sub_matches.is_present("noconfirm"));
+                    let mut profile =
+                        active::get_active_profile(&config)?.ok_or(ActiveError::NoActiveProfile)?;
+
+                    let settings: Vec<PathBuf> = match sub_matches.values_of("SETTINGS") {
+                        Some(settings) => settings.map(PathBuf::from).collect(),
+                        None => {
+                            // only settings that aren't enabled yet can be enabled
+                            let items = setting::list(&config)?
+                                .into_iter()
+                                .filter(|path| {
+                                    !profile.settings().iter().any(|s| s.path() == *path)
+                                })
+                                .map(|path| Item {
+                                    name: setting::parse(&config, &path)
+                                        .map(|s| s.name())
+                                        .unwrap_or_default(),
+                                    path,
+                                })
+                                .collect();
+
+                            pick::many("pick settings to enable", items)?
+                        }
+                    };
+
+                    let noconfirm = sub_matches.is_present("noconfirm");
+
+                    for path in settings {
+                        let enabled = profile.enable_setting(
+                            &path,
+                            |replaced, setting, target, key| {
+                                if noconfirm {
+                                    Ok(true)
+                                } else {
+                                    confirm_replace(replaced, setting, target, key)
+                                }
+                            },
+                            dry_run,
+                        )?;
+
+                        match enabled {
+                            Enabled::AlreadyEnabled => {
+                                println!("setting {:?} is already enabled", path)
                             }
 
-                            Ok(())
-                        } else {
-                            todo!("use quiz crate");
-                            // TODO: use quiz
+                            Enabled::Declined => (),
+
+                            Enabled::Enabled(overrides) => {
+                                for (overridden, overriding, target, key) in overrides {
+                                    println!(
+                                        "setting {:?} overrides {:?} from setting {:?} in target {:?}",
+                                        overriding, key, overridden, target
+                                    );
+                                }
+                            }
                         }
-                    } else {
-                        println!("No active profile");
-                        std::process::exit(1);
                     }
+
+                    if dry_run {
+                        print_dry_run(&profile.name(), profile.dry_run(&engine)?);
+                    }
+
+                    Ok(())
                 }
 
                 ("disable", Some(sub_matches)) => {
-                    if let Some(mut profile) = active::get_active_profile()? {
-                        if let Some(settings) = sub_matches.values_of("SETTINGS") {
-                            for setting in settings {
-                                profile.disable_setting(setting)?;
-                            }
+                    let mut profile =
+                        active::get_active_profile(&config)?.ok_or(ActiveError::NoActiveProfile)?;
+
+                    let settings: Vec<PathBuf> = match sub_matches.values_of("SETTINGS") {
+                        Some(settings) => settings.map(PathBuf::from).collect(),
+                        None => {
+                            let items = profile
+                                .settings()
+                                .iter()
+                                .map(|setting| Item {
+                                    name: setting.name(),
+                                    path: setting.path(),
+                                })
+                                .collect();
 
-                            Ok(())
-                        } else {
-                            todo!("use quiz crate");
-                            // TODO: use quiz
+                            pick::many("pick settings to disable", items)?
                         }
+                    };
+
+                    let mut changes: Vec<(PathBuf, FileChange)> = Vec::new();
+
+                    for setting in settings {
+                        // a later setting decides what happens to a file both of them touch
+                        for (path, change) in profile.disable_setting(setting, &engine, dry_run)? {
+                            changes.retain(|(p, _)| *p != path);
+                            changes.push((path, change));
+                        }
+                    }
+
+                    if dry_run {
+                        print_dry_run(&profile.name(), changes);
+                    }
+
+                    Ok(())
+                }
+
+                ("list", Some(sub_matches)) => {
+                    let enabled: Vec<PathBuf> = if sub_matches.is_present("enabled")
+                        || sub_matches.is_present("available")
+                    {
+                        active::get_active_profile(&config)?
+                            .ok_or(ActiveError::NoActiveProfile)?
+                            .settings()
+                            .iter()
+                            .map(|setting| setting.path())
+                            .collect()
                     } else {
-                        println!("No active profile");
-                        std::process::exit(1);
+                        Vec::new()
+                    };
+
+                    for path in setting::list(&config)? {
+                        if sub_matches.is_present("enabled") && !enabled.contains(&path)
+                            || sub_matches.is_present("available") && enabled.contains(&path)
+                        {
+                            continue;
+                        }
+
+                        list_entry(&path, show::setting(&config, &path));
                     }
+
+                    Ok(())
+                }
+
+                _ => Ok(()),
+            }
+        }
+
+        ("template", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("list", Some(_sub_matches)) => {
+                for path in template::list(&config)? {
+                    list_entry(&path, show::template(&config, &path));
                 }
 
-                _ => Ok(())
+                Ok(())
+            }
+
+            _ => Ok(()),
+        },
+
+        ("check", ..) => {
+            let problems = check::check(&config, &engine)?;
+
+            for problem in &problems {
+                eprintln!("{}", problem);
+                print_causes(problem);
+            }
+
+            if problems.is_empty() {
+                Ok(())
+            } else {
+                Err(CrateError::CheckFailed(problems.len()))
             }
         }
 
+        // ITEM is a required argument
+        ("show", Some(sub_matches)) => {
+            show::item(&config, sub_matches.value_of("ITEM").unwrap_or_default())
+        }
+
         ("reload", ..) => {
-            if let Some(profile) = active::get_active_profile()? {
-                profile.apply(&engine);
+            let profile =
+                active::get_active_profile(&config)?.ok_or(ActiveError::NoActiveProfile)?;
+
+            if dry_run {
+                print_dry_run(&profile.name(), profile.dry_run(&engine)?);
                 Ok(())
             } else {
-                println!("No active profile");
-                std::process::exit(1);
+                profile.apply(&engine)
+            }
+        }
+
+        ("status", ..) => {
+            let profile = match active::get_active_profile(&config)? {
+                Some(profile) => profile,
+                None => {
+                    println!("no active profile");
+                    return Ok(());
+                }
+            };
+
+            println!(
+                "profile {:?} ({})",
+                profile.name(),
+                profile.path().display()
+            );
+
+            if let Some(applied) = State::load(&config)?.applied() {
+                println!("last applied {}", applied.format("%Y-%m-%d %H:%M:%S"));
             }
+
+            println!();
+            println!("settings:");
+
+            for setting in profile.settings() {
+                println!("    {:?} ({})", setting.name(), setting.path().display());
+            }
+
+            println!();
+            println!("outputs:");
+
+            for (path, status) in profile.status(&engine)? {
+                println!("    {:<14} {}", status, path.display());
+            }
+
+            Ok(())
+        }
+
+        ("backup", Some(sub_matches)) => match sub_matches.subcommand() {
+            ("list", Some(_sub_matches)) => {
+                for (id, files) in backup::list(&config)? {
+                    println!("{}", id);
+
+                    for file in files {
+                        println!("    {}", file.display());
+                    }
+                }
+
+                Ok(())
+            }
+
+            ("restore", Some(sub_matches)) => backup::restore(
+                &config,
+                // ID is a required argument
+                sub_matches.value_of("ID").unwrap_or_default(),
+                sub_matches.value_of("PATH"),
+            ),
+
+            _ => Ok(()),
+        },
+
+        ("diff", Some(sub_matches)) => {
+            let profile = match sub_matches.value_of("PROFILE") {
+                Some(path) => profile::parse(&config, path)?,
+                None => active::get_active_profile(&config)?.ok_or(ActiveError::NoActiveProfile)?,
+            };
+
+            for diff in profile.diff(&engine)? {
+                print_diff(&diff);
+            }
+
+            Ok(())
+        }
+
+        ("explain", Some(sub_matches)) => {
+            let profile =
+                active::get_active_profile(&config)?.ok_or(ActiveError::NoActiveProfile)?;
+
+            // TEMPLATE is a required argument
+            let template = sub_matches.value_of("TEMPLATE").unwrap_or_default();
+            let mut explanations = profile.explain(template, &engine)?;
+
+            if let Some(key) = sub_matches.value_of("KEY") {
+                explanations.retain(|k, _| k == key);
+
+                if explanations.is_empty() {
+                    println!("{:?} is not set by any setting", key);
+                }
+            }
+
+            let setting_name = |path: &std::path::Path| {
+                profile
+                    .settings()
+                    .iter()
+                    .find(|s| s.path() == path)
+                    .map(|s| s.name())
+                    .unwrap_or_default()
+            };
+
+            for (key, explanation) in explanations {
+                let value = serde_json::to_string(&explanation.value).unwrap_or_default();
+                println!("{} = {}", key, value);
+
+                match &explanation.setting {
+                    Some(path) => println!(
+                        "    from setting {:?} ({})",
+                        setting_name(path),
+                        path.display()
+                    ),
+                    None => println!("    default from the template"),
+                }
+
+                match &explanation.origin {
+                    Some(Origin::Profile) => println!("    set by the profile"),
+                    Some(Origin::Target(path)) => println!(
+                        "    in target {}",
+                        path.strip_prefix(config.templates_dir())
+                            .unwrap_or(path)
+                            .display()
+                    ),
+                    Some(Origin::Global) => println!("    in [global]"),
+                    None => (),
+                }
+
+                if let Some(script) = &explanation.script {
+                    println!("    produced by script {}", script.display());
+                }
+
+                for path in &explanation.overrides {
+                    println!(
+                        "    overrides setting {:?} ({})",
+                        setting_name(path),
+                        path.display()
+                    );
+                }
+            }
+
+            Ok(())
         }
 
-        _ => Ok(())
+        _ => Ok(()),
     }
 }
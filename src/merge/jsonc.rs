@@ -0,0 +1,617 @@
+use crate::errors::MergeError;
+use crate::merge::nest;
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+use std::path::Path;
+
+/// A table in a JSON file along with where each of its members is written
+struct Object {
+    open: usize,
+    close: usize,
+    members: Vec<Member>,
+}
+
+/// A member of a table in a JSON file, where positions are byte offsets into the file
+struct Member {
+    key: String,
+    start: usize,
+    value_start: usize,
+    value_end: usize,
+    object: Option<Object>,
+    comma: Option<usize>,
+}
+
+/// Blanks out comments with spaces, so the offsets stay the same as in the file
+fn blank_comments(contents: &str) -> Vec<u8> {
+    let mut bytes = contents.as_bytes().to_vec();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'"', _) => i = skip_string(&bytes, i),
+
+            (b'/', Some(b'/')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    bytes[i] = b' ';
+                    i += 1;
+                }
+            }
+
+            (b'/', Some(b'*')) => {
+                let end = contents[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + 2 + end + 2);
+
+                for byte in &mut bytes[i..end] {
+                    // line breaks are kept so comments spanning lines don't join them
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+
+                i = end;
+            }
+
+            _ => i += 1,
+        }
+    }
+
+    bytes
+}
+
+/// Blanks out commas that come right before the end of a table or array
+fn blank_trailing_commas(bytes: &mut [u8]) {
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => i = skip_string(bytes, i),
+
+            b',' => {
+                let next = bytes[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+
+                if matches!(next, Some(b'}') | Some(b']')) {
+                    bytes[i] = b' ';
+                }
+
+                i += 1;
+            }
+
+            _ => i += 1,
+        }
+    }
+}
+
+/// Get the offset right after the string starting at an offset
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+
+    i
+}
+
+/// Get the offset right after a value that isn't a table, which is all that editing needs to know
+/// about arrays, strings and plain values
+fn skip_value(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start)? {
+        b'"' => Some(skip_string(bytes, start)),
+
+        b'[' | b'{' => {
+            let mut depth = 0;
+            let mut i = start;
+
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = skip_string(bytes, i);
+                        continue;
+                    }
+                    b'[' | b'{' => depth += 1,
+                    b']' | b'}' => depth -= 1,
+                    _ => (),
+                }
+
+                i += 1;
+
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+
+            None
+        }
+
+        _ => {
+            let len = bytes[start..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || b",}]".contains(b))
+                .unwrap_or(bytes.len() - start);
+
+            Some(start + len)
+        }
+    }
+}
+
+/// Reads the table starting at an offset, with comments already blanked out
+fn parse_object(bytes: &[u8], open: usize) -> Option<Object> {
+    let mut members = Vec::new();
+    let mut i = skip_whitespace(bytes, open + 1);
+
+    while *bytes.get(i)? != b'}' {
+        let start = i;
+        let key_end = skip_string(bytes, start);
+        let key = serde_json::from_slice(&bytes[start..key_end]).ok()?;
+
+        i = skip_whitespace(bytes, key_end);
+        if *bytes.get(i)? != b':' {
+            return None;
+        }
+
+        let value_start = skip_whitespace(bytes, i + 1);
+        let object = match bytes.get(value_start)? {
+            b'{' => Some(parse_object(bytes, value_start)?),
+            _ => None,
+        };
+        let value_end = match &object {
+            Some(object) => object.close + 1,
+            None => skip_value(bytes, value_start)?,
+        };
+
+        i = skip_whitespace(bytes, value_end);
+        let comma = Some(i).filter(|i| bytes.get(*i) == Some(&b','));
+        if let Some(comma) = comma {
+            i = skip_whitespace(bytes, comma + 1);
+        }
+
+        members.push(Member {
+            key,
+            start,
+            value_start,
+            value_end,
+            object,
+            comma,
+        });
+    }
+
+    Some(Object {
+        open,
+        close: i,
+        members,
+    })
+}
+
+/// Reads the top level table of a file
+fn parse_root(path: &Path, bytes: &[u8]) -> Result<Object, MergeError> {
+    let open = skip_whitespace(bytes, 0);
+
+    parse_object(bytes, open).ok_or_else(|| MergeError::NotATable(path.to_owned()))
+}
+
+/// Parses JSON that may have comments and trailing commas in it
+pub fn parse(path: &Path, contents: &str) -> Result<Value, MergeError> {
+    let mut bytes = blank_comments(contents);
+    blank_trailing_commas(&mut bytes);
+
+    serde_json::from_slice(&bytes)
+        .map_err(|err| MergeError::ErrorParsingTarget(path.to_owned(), err.into()))
+}
+
+/// Get the whitespace a line starts with
+fn indentation(contents: &str, offset: usize) -> &str {
+    let start = contents[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let len = contents[start..]
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(contents.len() - start);
+
+    &contents[start..start + len]
+}
+
+/// Get the indentation the file uses for each level, two spaces if nothing is indented yet
+fn indent_unit(contents: &str) -> &str {
+    contents
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ")
+}
+
+/// Get the offset of the line break ending a line, if nothing but blanked out comments follows
+/// the offset on it
+fn blank_until_newline(bytes: &[u8], offset: usize) -> Option<usize> {
+    let end = bytes[offset..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |i| offset + i);
+
+    Some(end).filter(|end| bytes[offset..*end].iter().all(u8::is_ascii_whitespace))
+}
+
+/// Writes a value indented to sit on a line with the given indentation
+fn render(value: &Value, indent: &str, unit: &str) -> String {
+    let mut out = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(unit.as_bytes()));
+
+    // values come from serde_json itself so writing them can't fail
+    let _ = value.serialize(&mut serializer);
+
+    String::from_utf8_lossy(&out).replace('\n', &format!("\n{}", indent))
+}
+
+/// Writes a member of a table on a line with the given indentation
+fn member(key: &str, value: &Value, indent: &str, unit: &str) -> String {
+    format!("{}: {}", Value::from(key), render(value, indent, unit))
+}
+
+/// Writes a member of a table that is written on a single line
+fn compact_member(key: &str, value: &Value) -> String {
+    format!("{}: {}", Value::from(key), value)
+}
+
+/// Finds the table a key path leads into as far as it exists, returning it along with how many
+/// keys of the path it took
+fn find<'a>(mut object: &'a Object, path: &[String]) -> (&'a Object, usize) {
+    for (depth, key) in path.iter().enumerate() {
+        match object.members.iter().find(|m| m.key == *key) {
+            Some(Member {
+                object: Some(inner),
+                ..
+            }) if depth + 1 < path.len() => object = inner,
+            _ => return (object, depth),
+        }
+    }
+
+    (object, path.len())
+}
+
+/// Sets a single value at a key path, replacing whatever isn't a table along the way
+fn set(
+    path: &Path,
+    contents: &str,
+    key_path: &[String],
+    value: &Value,
+) -> Result<String, MergeError> {
+    let bytes = blank_comments(contents);
+    let unit = indent_unit(contents);
+    let root = parse_root(path, &bytes)?;
+    let (object, depth) = find(&root, key_path);
+    let key = &key_path[depth];
+    let value = nest(&key_path[depth + 1..], value);
+    let mut contents = contents.to_owned();
+
+    // the value is replaced along with the tables the rest of the path still needs
+    if let Some(m) = object.members.iter().find(|m| m.key == *key) {
+        let indent = indentation(&contents, m.start).to_owned();
+        contents.replace_range(m.value_start..m.value_end, &render(&value, &indent, unit));
+
+        return Ok(contents);
+    }
+
+    let inside = &contents[object.open + 1..object.close];
+
+    match object.members.last() {
+        // empty tables get opened up over several lines
+        None if inside.trim().is_empty() => {
+            let outer = indentation(&contents, object.open).to_owned();
+            let indent = format!("{}{}", outer, unit);
+            let member = member(key, &value, &indent, unit);
+
+            contents.replace_range(
+                object.open + 1..object.close,
+                &format!("\n{}{}\n{}", indent, member, outer),
+            );
+        }
+
+        None => {
+            let indent = format!("{}{}", indentation(&contents, object.open), unit);
+            let member = member(key, &value, &indent, unit);
+
+            contents.insert_str(object.open + 1, &format!("\n{}{}", indent, member));
+        }
+
+        Some(last) if !inside.contains('\n') => match last.comma {
+            Some(comma) => {
+                contents.insert_str(comma + 1, &format!(" {},", compact_member(key, &value)))
+            }
+            None => contents.insert_str(
+                last.value_end,
+                &format!(", {}", compact_member(key, &value)),
+            ),
+        },
+
+        Some(last) => {
+            let indent = indentation(&contents, last.start).to_owned();
+            let member = member(key, &value, &indent, unit);
+
+            // a trailing comma after the last member stays after the new one
+            match last.comma {
+                Some(comma) => {
+                    let at = blank_until_newline(&bytes, comma + 1).unwrap_or(comma + 1);
+                    contents.insert_str(at, &format!("\n{}{},", indent, member));
+                }
+
+                None => {
+                    let at = blank_until_newline(&bytes, last.value_end).unwrap_or(last.value_end);
+                    contents.insert_str(at, &format!("\n{}{}", indent, member));
+                    contents.insert(last.value_end, ',');
+                }
+            }
+        }
+    }
+
+    Ok(contents)
+}
+
+/// Removes the member at a key path, returning `None` if the file doesn't have it
+fn unset(path: &Path, contents: &str, key_path: &[String]) -> Result<Option<String>, MergeError> {
+    let bytes = blank_comments(contents);
+    let root = parse_root(path, &bytes)?;
+    let (object, depth) = find(&root, key_path);
+
+    if depth + 1 != key_path.len() {
+        return Ok(None);
+    }
+
+    let index = match object.members.iter().position(|m| m.key == key_path[depth]) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+
+    let m = &object.members[index];
+    let mut contents = contents.to_owned();
+
+    match (m.comma, index.checked_sub(1)) {
+        // a member followed by a comma goes along with it, and with its whole line if nothing
+        // else is on it
+        (Some(comma), _) => {
+            let line_start = contents[..m.start].rfind('\n').map_or(0, |i| i + 1);
+            let own_line = bytes[line_start..m.start]
+                .iter()
+                .all(u8::is_ascii_whitespace);
+
+            match blank_until_newline(&bytes, comma + 1) {
+                Some(end) if own_line => {
+                    let next_line = contents[end..]
+                        .find('\n')
+                        .map_or(contents.len(), |i| end + i + 1);
+                    contents.replace_range(line_start..next_line, "");
+                }
+
+                _ => {
+                    let end = comma
+                        + 1
+                        + bytes[comma + 1..]
+                            .iter()
+                            .take_while(|b| **b == b' ' || **b == b'\t')
+                            .count();
+                    contents.replace_range(m.start..end, "");
+                }
+            }
+        }
+
+        // the last member takes the comma of the one before it
+        (None, Some(previous)) => {
+            let previous = &object.members[previous];
+            let start = previous.comma.unwrap_or(previous.value_end);
+
+            contents.replace_range(start..m.value_end, "");
+        }
+
+        (None, None) => contents.replace_range(object.open + 1..object.close, ""),
+    }
+
+    Ok(Some(contents))
+}
+
+/// Checks whether the member at a key path is a table without any members
+fn is_empty_table(path: &Path, contents: &str, key_path: &[String]) -> Result<bool, MergeError> {
+    let bytes = blank_comments(contents);
+    let root = parse_root(path, &bytes)?;
+    let (object, depth) = find(&root, key_path);
+
+    Ok(depth + 1 == key_path.len()
+        && object
+            .members
+            .iter()
+            .find(|m| m.key == key_path[depth])
+            .and_then(|m| m.object.as_ref())
+            .is_some_and(|o| o.members.is_empty()))
+}
+
+/// Sets every value at its key path, keeping comments and formatting
+pub fn merge(
+    path: &Path,
+    contents: &str,
+    leaves: &[(Vec<String>, &Value)],
+) -> Result<String, MergeError> {
+    let mut contents = if contents.trim().is_empty() {
+        String::from("{}\n")
+    } else {
+        contents.to_owned()
+    };
+
+    for (key_path, value) in leaves {
+        contents = set(path, &contents, key_path, value)?;
+    }
+
+    Ok(contents)
+}
+
+/// Removes the members at every key path along with the tables that are left empty, keeping
+/// comments and formatting
+pub fn remove(path: &Path, contents: &str, keys: &[Vec<String>]) -> Result<String, MergeError> {
+    let mut contents = contents.to_owned();
+
+    for key in keys {
+        let mut key = key.to_owned();
+
+        while let Some(removed) = unset(path, &contents, &key)? {
+            contents = removed;
+            key.pop();
+
+            // tables only stay if something else is still in them
+            if key.is_empty() || !is_empty_table(path, &contents, &key)? {
+                break;
+            }
+        }
+    }
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(path: &str) -> Vec<String> {
+        path.split('.').map(str::to_owned).collect()
+    }
+
+    fn set_at(contents: &str, path: &str, value: Value) -> String {
+        set(Path::new("test.json"), contents, &key(path), &value).unwrap()
+    }
+
+    fn unset_at(contents: &str, path: &str) -> Option<String> {
+        unset(Path::new("test.json"), contents, &key(path)).unwrap()
+    }
+
+    fn remove_all(contents: &str, paths: &[&str]) -> String {
+        let keys: Vec<Vec<String>> = paths.iter().map(|path| key(path)).collect();
+        remove(Path::new("test.json"), contents, &keys).unwrap()
+    }
+
+    #[test]
+    fn parse_allows_comments_and_trailing_commas() {
+        let contents = "{\n  // a comment\n  \"a\": [1, 2,], /* another */\n  \"b\": \"//\",\n}\n";
+
+        assert_eq!(
+            parse(Path::new("test.json"), contents).unwrap(),
+            json!({ "a": [1, 2], "b": "//" })
+        );
+    }
+
+    #[test]
+    fn set_replaces_a_value_keeping_comments() {
+        let contents = "{\n  // size\n  \"size\": 10, // inline\n  \"theme\": \"dark\"\n}\n";
+
+        assert_eq!(
+            set_at(contents, "size", json!(14)),
+            "{\n  // size\n  \"size\": 14, // inline\n  \"theme\": \"dark\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn set_adds_a_member_after_a_trailing_comma() {
+        assert_eq!(
+            set_at("{\n  \"a\": 1,\n}\n", "b", json!(2)),
+            "{\n  \"a\": 1,\n  \"b\": 2,\n}\n"
+        );
+    }
+
+    #[test]
+    fn set_adds_a_member_after_a_comment() {
+        assert_eq!(
+            set_at("{\n  \"a\": 1 // one\n}\n", "b", json!(2)),
+            "{\n  \"a\": 1, // one\n  \"b\": 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn set_keeps_single_line_objects_on_one_line() {
+        assert_eq!(set_at("{\"a\": 1}", "b", json!(2)), "{\"a\": 1, \"b\": 2}");
+        assert_eq!(
+            set_at("{\"x\": {\"y\": 1}}", "x.z", json!(2)),
+            "{\"x\": {\"y\": 1, \"z\": 2}}"
+        );
+    }
+
+    #[test]
+    fn set_creates_nested_tables() {
+        assert_eq!(
+            set_at("{\n  \"a\": 1\n}\n", "x.y", json!(true)),
+            "{\n  \"a\": 1,\n  \"x\": {\n    \"y\": true\n  }\n}\n"
+        );
+        assert_eq!(set_at("{}\n", "a", json!(1)), "{\n  \"a\": 1\n}\n");
+    }
+
+    #[test]
+    fn set_follows_the_indentation_of_the_file() {
+        assert_eq!(
+            set_at("{\n\t\"a\": {\n\t\t\"b\": 1\n\t}\n}\n", "a.c", json!(2)),
+            "{\n\t\"a\": {\n\t\t\"b\": 1,\n\t\t\"c\": 2\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn unset_removes_a_line_along_with_its_comment() {
+        assert_eq!(
+            unset_at("{\n  \"a\": 1, // one\n  \"b\": 2\n}\n", "a").unwrap(),
+            "{\n  \"b\": 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn unset_takes_the_comma_before_the_last_member() {
+        assert_eq!(
+            unset_at("{\n  \"a\": 1,\n  \"b\": 2\n}\n", "b").unwrap(),
+            "{\n  \"a\": 1\n}\n"
+        );
+    }
+
+    #[test]
+    fn unset_removes_members_of_single_line_objects() {
+        assert_eq!(unset_at("{\"a\": 1, \"b\": 2}", "a").unwrap(), "{\"b\": 2}");
+    }
+
+    #[test]
+    fn unset_leaves_missing_keys_alone() {
+        assert_eq!(unset_at("{\"a\": 1}", "b"), None);
+        assert_eq!(unset_at("{\"a\": 1}", "a.b"), None);
+    }
+
+    #[test]
+    fn remove_keeps_a_trailing_comma() {
+        assert_eq!(
+            remove_all("{\n  \"a\": 1,\n  \"b\": 2,\n}\n", &["b"]),
+            "{\n  \"a\": 1,\n}\n"
+        );
+    }
+
+    #[test]
+    fn remove_prunes_tables_left_empty() {
+        let contents = "{\n  \"a\": 1,\n  \"x\": {\n    \"y\": {\n      \"z\": 2\n    }\n  }\n}\n";
+
+        assert_eq!(remove_all(contents, &["x.y.z"]), "{\n  \"a\": 1\n}\n");
+    }
+
+    #[test]
+    fn remove_keeps_tables_with_other_members() {
+        let contents = "{\n  \"x\": {\n    \"y\": 1,\n    \"z\": 2\n  }\n}\n";
+
+        assert_eq!(
+            remove_all(contents, &["x.y"]),
+            "{\n  \"x\": {\n    \"z\": 2\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn remove_the_last_member() {
+        assert_eq!(remove_all("{\n  \"a\": 1\n}\n", &["a"]), "{}\n");
+    }
+}
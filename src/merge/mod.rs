@@ -0,0 +1,185 @@
+mod jsonc;
+mod toml_file;
+mod yaml;
+
+use crate::errors::MergeError;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// The format of a structured file that settings deep-merge values into
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[serde(alias = "jsonc")]
+    Json,
+    #[serde(alias = "yml")]
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Gets the format of a structured file from its extension
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "json" | "jsonc" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the contents of a structured file, which has to be a table at the top level
+pub fn parse(
+    format: Format,
+    path: &Path,
+    contents: &str,
+) -> Result<Map<String, Value>, MergeError> {
+    // a file that is empty or does not exist yet gets created from the merged values alone
+    if contents.trim().is_empty() {
+        return Ok(Map::new());
+    }
+
+    let failure = |err: Box<dyn std::error::Error + Send + Sync>| {
+        MergeError::ErrorParsingTarget(path.to_owned(), err)
+    };
+
+    let value: Value = match format {
+        Format::Json => jsonc::parse(path, contents)?,
+        Format::Yaml => serde_yaml::from_str(contents).map_err(|err| failure(err.into()))?,
+        Format::Toml => toml::from_str(contents).map_err(|err| failure(err.into()))?,
+    };
+
+    match value {
+        Value::Object(map) => Ok(map),
+        _ => Err(MergeError::NotATable(path.to_owned())),
+    }
+}
+
+/// Sets every value of a table in the contents of a structured file, keeping the rest of the file
+/// as it was written
+pub fn merge(
+    format: Format,
+    path: &Path,
+    contents: &str,
+    values: Map<String, Value>,
+) -> Result<String, MergeError> {
+    // make sure the file is a table before editing it
+    parse(format, path, contents)?;
+
+    // settings come with their values in any order, so new keys are added sorted
+    let mut leaves = Vec::new();
+    key_paths(&values, &[], &mut leaves);
+    leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    with_lf(contents, |contents| match format {
+        Format::Json => jsonc::merge(path, contents, &leaves),
+        Format::Yaml => yaml::merge(path, contents, &leaves),
+        Format::Toml => toml_file::merge(path, contents, &leaves),
+    })
+}
+
+/// Removes key paths from the contents of a structured file along with the tables that are left
+/// empty, keeping the rest of the file as it was written
+pub fn remove(
+    format: Format,
+    path: &Path,
+    contents: &str,
+    keys: &[Vec<String>],
+) -> Result<String, MergeError> {
+    parse(format, path, contents)?;
+
+    with_lf(contents, |contents| match format {
+        Format::Json => jsonc::remove(path, contents, keys),
+        Format::Yaml => yaml::remove(path, contents, keys),
+        Format::Toml => toml_file::remove(path, contents, keys),
+    })
+}
+
+/// Puts keys of a structured file back to the values they had, in order, removing the ones that
+/// weren't there
+pub fn restore(
+    format: Format,
+    path: &Path,
+    contents: &str,
+    originals: &[(Vec<String>, Option<Value>)],
+) -> Result<String, MergeError> {
+    let mut contents = contents.to_owned();
+
+    for (key, value) in originals {
+        // tables are put back whole rather than merged into what is there now
+        if matches!(value, None | Some(Value::Object(_))) {
+            contents = remove(format, path, &contents, &[key.to_owned()])?;
+        }
+
+        if let Some(Value::Object(values)) = value.as_ref().map(|value| nest(key, value)) {
+            contents = merge(format, path, &contents, values)?;
+        }
+    }
+
+    Ok(contents)
+}
+
+/// Get the value at a key path
+pub fn get<'a>(map: &'a Map<String, Value>, key: &[String]) -> Option<&'a Value> {
+    let (last, path) = key.split_last()?;
+    let mut map = map;
+
+    for k in path {
+        map = map.get(k)?.as_object()?;
+    }
+
+    map.get(last)
+}
+
+/// Edits contents with lf line breaks, putting back crlf ones if the file used them
+fn with_lf<F>(contents: &str, edit: F) -> Result<String, MergeError>
+where
+    F: FnOnce(&str) -> Result<String, MergeError>,
+{
+    if !contents.contains("\r\n") {
+        return edit(contents);
+    }
+
+    Ok(edit(&contents.replace("\r\n", "\n"))?.replace('\n', "\r\n"))
+}
+
+/// Collects the key path of every value that isn't a table with values of its own, in the order
+/// the keys are written
+fn key_paths<'a>(
+    map: &'a Map<String, Value>,
+    prefix: &[String],
+    leaves: &mut Vec<(Vec<String>, &'a Value)>,
+) {
+    for (k, v) in map {
+        let mut path = prefix.to_vec();
+        path.push(k.to_owned());
+
+        match v {
+            Value::Object(map) if !map.is_empty() => key_paths(map, &path, leaves),
+            _ => leaves.push((path, v)),
+        }
+    }
+}
+
+/// Wraps a value in a table for every key of a path
+fn nest(path: &[String], value: &Value) -> Value {
+    path.iter().rev().fold(value.to_owned(), |value, key| {
+        let mut map = Map::new();
+        map.insert(key.to_owned(), value);
+        Value::Object(map)
+    })
+}
+
+/// Merges a table into another, replacing values that aren't tables on both sides
+pub fn deep_merge(base: &mut Map<String, Value>, patch: Map<String, Value>) {
+    for (k, v) in patch {
+        match (base.get_mut(&k), v) {
+            (Some(Value::Object(base)), Value::Object(patch)) => deep_merge(base, patch),
+            (_, v) => {
+                base.insert(k, v);
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::errors::MergeError;
+use serde_json::Value;
+use std::path::Path;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table};
+
+fn parse_document(path: &Path, contents: &str) -> Result<DocumentMut, MergeError> {
+    contents.parse().map_err(|err: toml_edit::TomlError| {
+        MergeError::ErrorParsingTarget(path.to_owned(), err.into())
+    })
+}
+
+/// Converts a value into a toml value, which has no null
+fn toml_value(path: &Path, value: &Value) -> Result<toml_edit::Value, MergeError> {
+    Ok(match value {
+        Value::Null => return Err(MergeError::NullValue(path.to_owned())),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => n.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| toml_value(path, v))
+            .collect::<Result<Array, _>>()?
+            .into(),
+        Value::Object(map) => {
+            let mut table = InlineTable::new();
+
+            for (k, v) in map {
+                table.insert(k, toml_value(path, v)?);
+            }
+
+            table.into()
+        }
+    })
+}
+
+/// Get a new table to put keys in, written inline when the table holding it is
+fn new_table(inline: bool) -> Item {
+    if inline {
+        Item::Value(InlineTable::new().into())
+    } else {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        Item::Table(table)
+    }
+}
+
+/// Sets a single value at a key path inside a table, replacing whatever isn't a table along the way
+fn set(path: &Path, item: &mut Item, key_path: &[String], value: &Value) -> Result<(), MergeError> {
+    let inline = item.is_inline_table();
+    let table = match item.as_table_like_mut() {
+        Some(table) => table,
+        None => return Ok(()),
+    };
+
+    let (key, rest) = match key_path.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    if !rest.is_empty() {
+        let inner = table.entry(key).or_insert_with(|| new_table(inline));
+
+        if !inner.is_table_like() {
+            *inner = new_table(inline);
+        }
+
+        return set(path, inner, rest, value);
+    }
+
+    let mut new = toml_value(path, value)?;
+
+    // comments next to a value stay when it changes
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(old) => {
+            *new.decor_mut() = old.decor().to_owned();
+            *old = new;
+        }
+
+        None => {
+            table.insert(key, Item::Value(new));
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the key at a key path, returning whether it was there
+fn unset(item: &mut Item, key_path: &[String]) -> bool {
+    let table = match item.as_table_like_mut() {
+        Some(table) => table,
+        None => return false,
+    };
+
+    let (key, rest) = match key_path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    if rest.is_empty() {
+        return table.remove(key).is_some();
+    }
+
+    let removed = match table.get_mut(key) {
+        Some(inner) => unset(inner, rest),
+        None => false,
+    };
+
+    // tables only stay if something else is still in them
+    if removed
+        && table
+            .get(key)
+            .and_then(Item::as_table_like)
+            .is_some_and(|t| t.is_empty())
+    {
+        table.remove(key);
+    }
+
+    removed
+}
+
+/// Sets every value at its key path, keeping comments and formatting
+pub fn merge(
+    path: &Path,
+    contents: &str,
+    leaves: &[(Vec<String>, &Value)],
+) -> Result<String, MergeError> {
+    let mut document = parse_document(path, contents)?;
+
+    for (key_path, value) in leaves {
+        set(path, document.as_item_mut(), key_path, value)?;
+    }
+
+    Ok(document.to_string())
+}
+
+/// Removes the keys at every key path along with the tables that are left empty, keeping
+/// comments and formatting
+pub fn remove(path: &Path, contents: &str, keys: &[Vec<String>]) -> Result<String, MergeError> {
+    let mut document = parse_document(path, contents)?;
+
+    for key in keys {
+        unset(document.as_item_mut(), key);
+    }
+
+    Ok(document.to_string())
+}
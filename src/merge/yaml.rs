@@ -0,0 +1,613 @@
+use crate::errors::MergeError;
+use crate::merge::{parse, Format};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// A key of a block mapping in a YAML file along with the lines its value takes up
+struct Entry {
+    key: String,
+    /// The line the key is on
+    line: usize,
+    /// The offset right after the colon following the key on its line
+    colon: usize,
+    /// The line after the last one of the value
+    end: usize,
+    /// Whether nothing but a comment follows the key on its line, so the value is on the lines
+    /// after it
+    nested: bool,
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Checks whether a line holds anything other than whitespace and a comment
+fn is_content(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && !line.starts_with('#')
+}
+
+fn is_sequence_item(line: &str) -> bool {
+    let line = line.trim_start();
+    line == "-" || line.starts_with("- ")
+}
+
+/// Get the offset of the quote closing a quoted scalar that starts a string
+fn closing_quote(s: &str) -> Option<usize> {
+    let quote = s.chars().next()?;
+    let mut chars = s.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // single quotes are escaped by doubling them and double quotes with a backslash
+            '\'' if quote == '\'' && s[i + 1..].starts_with('\'') => {
+                chars.next();
+            }
+            '\\' if quote == '"' => {
+                chars.next();
+            }
+            c if c == quote => return Some(i),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Get the offset a comment starts at in what follows a key, if there is one
+fn comment_start(value: &str) -> Option<usize> {
+    let start = value.len() - value.trim_start().len();
+    let scalar = &value[start..];
+
+    // a quoted scalar can have anything in it
+    let from = match scalar.chars().next() {
+        Some('"') | Some('\'') => start + closing_quote(scalar)? + 1,
+        _ => start,
+    };
+
+    value[from..]
+        .char_indices()
+        .find(|(i, c)| *c == '#' && (from + i == 0 || value[..from + i].ends_with([' ', '\t'])))
+        .map(|(i, _)| from + i)
+}
+
+/// Splits a line into its key and the offset right after the colon following it, `None` if it
+/// isn't a key of a block mapping
+fn split_key(line: &str) -> Option<(String, usize)> {
+    let start = indentation(line);
+    let trimmed = &line[start..];
+
+    let (key, colon) = match trimmed.chars().next()? {
+        '"' | '\'' => {
+            let end = closing_quote(trimmed)?;
+            let key: String = serde_yaml::from_str(&trimmed[..=end]).ok()?;
+
+            trimmed[end + 1..].strip_prefix(':')?;
+            (key, end + 2)
+        }
+
+        '[' | '{' | '?' | '&' | '*' | '!' | '|' | '>' | '%' | '@' | '`' | '#' => return None,
+        _ if is_sequence_item(trimmed) => return None,
+
+        _ => {
+            let (i, _) = trimmed.match_indices(':').find(|(i, _)| {
+                let rest = &trimmed[i + 1..];
+                rest.is_empty() || rest.starts_with([' ', '\t'])
+            })?;
+
+            (trimmed[..i].trim_end().to_owned(), i + 1)
+        }
+    };
+
+    // a value next to the key is separated from the colon
+    match trimmed[colon..].chars().next() {
+        None | Some(' ') | Some('\t') => Some((key, start + colon)),
+        _ => None,
+    }
+}
+
+/// Reads the entries of the block mapping whose keys are indented by `indent` in a range of lines
+fn entries(
+    path: &Path,
+    lines: &[String],
+    start: usize,
+    end: usize,
+    indent: usize,
+) -> Result<Vec<Entry>, MergeError> {
+    let unsupported = || MergeError::UnsupportedFormatting(path.to_owned());
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+        if !is_content(line) {
+            continue;
+        }
+
+        // lines indented further, and sequence items right below a key, belong to the value of
+        // the key before them
+        let own = indentation(line);
+        let nested_item = own == indent
+            && is_sequence_item(line)
+            && entries.last().is_some_and(|entry| entry.nested);
+
+        if own > indent || nested_item {
+            entries.last_mut().ok_or_else(unsupported)?.end = i + 1;
+            continue;
+        }
+
+        if own < indent {
+            return Err(unsupported());
+        }
+
+        let (key, colon) = split_key(line).ok_or_else(unsupported)?;
+        let value = &line[colon..];
+        let value = comment_start(value).map_or(value, |c| &value[..c]);
+
+        entries.push(Entry {
+            key,
+            line: i,
+            colon,
+            end: i + 1,
+            nested: value.trim().is_empty(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Get the range of lines and the indentation of the block mapping the value of an entry is
+fn child(lines: &[String], entry: &Entry, indent: usize) -> Option<(usize, usize, usize)> {
+    if !entry.nested {
+        return None;
+    }
+
+    let first = lines[entry.line + 1..entry.end]
+        .iter()
+        .find(|line| is_content(line))?;
+    let child_indent = indentation(first);
+
+    if child_indent <= indent || is_sequence_item(first) {
+        return None;
+    }
+
+    Some((entry.line + 1, entry.end, child_indent))
+}
+
+/// Get the first line and the indentation of the top level mapping
+fn root(path: &Path, lines: &[String]) -> Result<(usize, usize), MergeError> {
+    let mut content = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| is_content(line));
+
+    let start = match content.next() {
+        Some((i, line)) if line.trim_end() == "---" || line.starts_with("--- #") => i + 1,
+        _ => 0,
+    };
+
+    // only a single document can be merged into
+    if lines[start..]
+        .iter()
+        .any(|line| line.starts_with("---") || line.trim_end() == "...")
+    {
+        return Err(MergeError::UnsupportedFormatting(path.to_owned()));
+    }
+
+    let indent = lines[start..]
+        .iter()
+        .find(|line| is_content(line))
+        .map_or(0, |line| indentation(line));
+
+    Ok((start, indent))
+}
+
+/// Get the indentation the file uses for each level, two spaces if nothing is indented yet
+fn indent_unit(lines: &[String]) -> usize {
+    lines
+        .iter()
+        .filter(|line| is_content(line) && !is_sequence_item(line))
+        .map(|line| indentation(line))
+        .filter(|indent| *indent > 0)
+        .min()
+        .unwrap_or(2)
+}
+
+/// Writes a key along with its value, starting with the key and its colon as written
+fn render(
+    path: &Path,
+    key: &str,
+    value: &Value,
+    indent: usize,
+    unit: usize,
+) -> Result<Vec<String>, MergeError> {
+    let yaml = serde_yaml::to_string(value)
+        .map_err(|err| MergeError::ErrorSerializingTarget(path.to_owned(), err.into()))?;
+    let pad = " ".repeat(indent);
+    let pad_line = |pad: &str, line: &str| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("{}{}", pad, line)
+        }
+    };
+
+    let block = match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        _ => false,
+    };
+
+    // tables and arrays go on the lines below the key, anything else starts next to it
+    if block {
+        let inner = " ".repeat(indent + unit);
+
+        Ok(std::iter::once(format!("{}{}", pad, key))
+            .chain(yaml.lines().map(|line| pad_line(&inner, line)))
+            .collect())
+    } else {
+        let mut lines = yaml.lines();
+        let first = format!("{}{} {}", pad, key, lines.next().unwrap_or_default());
+
+        Ok(std::iter::once(first)
+            .chain(lines.map(|line| pad_line(&pad, line)))
+            .collect())
+    }
+}
+
+/// Writes a key the way a new entry starts
+fn new_key(key: &str) -> String {
+    let yaml = serde_yaml::to_string(key).unwrap_or_else(|_| format!("{:?}", key));
+    format!("{}:", yaml.trim_end())
+}
+
+/// Sets a single value at a key path, replacing whatever isn't a table along the way
+fn set_value(map: &mut Map<String, Value>, key_path: &[String], value: &Value) {
+    match key_path.split_first() {
+        Some((key, [])) => {
+            map.insert(key.to_owned(), value.to_owned());
+        }
+
+        Some((key, rest)) => {
+            let inner = map
+                .entry(key.to_owned())
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            if !inner.is_object() {
+                *inner = Value::Object(Map::new());
+            }
+
+            if let Value::Object(inner) = inner {
+                set_value(inner, rest, value);
+            }
+        }
+
+        None => (),
+    }
+}
+
+/// Removes the key at a key path along with the tables left empty, returning whether it was there
+fn unset_value(map: &mut Map<String, Value>, key_path: &[String]) -> bool {
+    let (key, rest) = match key_path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    if rest.is_empty() {
+        return map.shift_remove(key).is_some();
+    }
+
+    let removed = match map.get_mut(key) {
+        Some(Value::Object(inner)) => unset_value(inner, rest),
+        _ => false,
+    };
+
+    // tables only stay if something else is still in them
+    if removed && matches!(map.get(key), Some(Value::Object(inner)) if inner.is_empty()) {
+        map.shift_remove(key);
+    }
+
+    removed
+}
+
+/// Sets a value at a key path inside the block mapping whose keys are indented by `indent` in a
+/// range of lines, where `current` is what the mapping holds
+fn set(
+    path: &Path,
+    lines: &mut Vec<String>,
+    (start, end, indent): (usize, usize, usize),
+    unit: usize,
+    key_path: &[String],
+    value: &Value,
+    current: &Map<String, Value>,
+) -> Result<(), MergeError> {
+    let (key, rest) = match key_path.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    let mapping = entries(path, lines, start, end, indent)?;
+    let existing = current.get(key);
+
+    let entry = match mapping.iter().find(|entry| entry.key == *key) {
+        Some(entry) => entry,
+        None => {
+            let mut new = Map::new();
+            set_value(&mut new, key_path, value);
+
+            let at = mapping.last().map_or(end, |entry| entry.end);
+            let rendered = render(path, &new_key(key), &new[key.as_str()], indent, unit)?;
+            lines.splice(at..at, rendered);
+
+            return Ok(());
+        }
+    };
+
+    if let (false, Some(range), Some(Value::Object(inner))) =
+        (rest.is_empty(), child(lines, entry, indent), existing)
+    {
+        return set(path, lines, range, unit, rest, value, inner);
+    }
+
+    // anything that isn't a block mapping is written again with the value set inside it
+    let new = if rest.is_empty() {
+        value.to_owned()
+    } else {
+        let mut inner = match existing {
+            Some(Value::Object(inner)) => inner.to_owned(),
+            _ => Map::new(),
+        };
+
+        set_value(&mut inner, rest, value);
+        Value::Object(inner)
+    };
+
+    let line = &lines[entry.line];
+    let mut rendered = render(
+        path,
+        line[indent..entry.colon].trim_end(),
+        &new,
+        indent,
+        unit,
+    )?;
+
+    // a comment next to a value stays when it changes
+    if let (1, 1) = (entry.end - entry.line, rendered.len()) {
+        if let Some(c) = comment_start(&line[entry.colon..]) {
+            let value = line[entry.colon..entry.colon + c].trim_end();
+            rendered[0].push_str(&line[entry.colon + value.len()..]);
+        }
+    }
+
+    lines.splice(entry.line..entry.end, rendered);
+
+    Ok(())
+}
+
+/// Removes the key at a key path from the block mapping whose keys are indented by `indent` in a
+/// range of lines along with the tables left empty, returning whether it was there
+fn unset(
+    path: &Path,
+    lines: &mut Vec<String>,
+    (start, end, indent): (usize, usize, usize),
+    unit: usize,
+    key_path: &[String],
+    current: &Map<String, Value>,
+) -> Result<bool, MergeError> {
+    let (key, rest) = match key_path.split_first() {
+        Some(split) => split,
+        None => return Ok(false),
+    };
+
+    let mapping = entries(path, lines, start, end, indent)?;
+    let entry = match mapping.iter().find(|entry| entry.key == *key) {
+        Some(entry) => entry,
+        None => return Ok(false),
+    };
+
+    if rest.is_empty() {
+        lines.drain(entry.line..entry.end);
+        return Ok(true);
+    }
+
+    let inner = match current.get(key) {
+        Some(Value::Object(inner)) => inner,
+        _ => return Ok(false),
+    };
+
+    if let Some((start, end, indent)) = child(lines, entry, indent) {
+        let before = lines.len();
+
+        if !unset(path, lines, (start, end, indent), unit, rest, inner)? {
+            return Ok(false);
+        }
+
+        // tables only stay if something else is still in them
+        let end = end - (before - lines.len());
+
+        if entries(path, lines, start, end, indent)?.is_empty() {
+            lines.drain(entry.line..end);
+        }
+
+        return Ok(true);
+    }
+
+    // a table written some other way is written again without the key
+    let mut inner = inner.to_owned();
+
+    if !unset_value(&mut inner, rest) {
+        return Ok(false);
+    }
+
+    if inner.is_empty() {
+        lines.drain(entry.line..entry.end);
+    } else {
+        let key = lines[entry.line][indent..entry.colon].trim_end().to_owned();
+        let rendered = render(path, &key, &Value::Object(inner), indent, unit)?;
+        lines.splice(entry.line..entry.end, rendered);
+    }
+
+    Ok(true)
+}
+
+/// Joins edited lines back together, checking that the file holds what editing it should have
+/// left in it
+fn finish(
+    path: &Path,
+    lines: Vec<String>,
+    expected: &Map<String, Value>,
+) -> Result<String, MergeError> {
+    let mut contents = lines.join("\n");
+
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+
+    // files written in ways the line based editing doesn't follow are left alone
+    match parse(Format::Yaml, path, &contents) {
+        Ok(map) if map == *expected => Ok(contents),
+        _ => Err(MergeError::UnsupportedFormatting(path.to_owned())),
+    }
+}
+
+/// Sets every value at its key path, keeping comments and formatting
+pub fn merge(
+    path: &Path,
+    contents: &str,
+    leaves: &[(Vec<String>, &Value)],
+) -> Result<String, MergeError> {
+    let mut expected = parse(Format::Yaml, path, contents)?;
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+    let unit = indent_unit(&lines);
+
+    for (key_path, value) in leaves {
+        let (start, indent) = root(path, &lines)?;
+        let range = (start, lines.len(), indent);
+
+        set(path, &mut lines, range, unit, key_path, value, &expected)?;
+        set_value(&mut expected, key_path, value);
+    }
+
+    finish(path, lines, &expected)
+}
+
+/// Removes the keys at every key path along with the tables that are left empty, keeping
+/// comments and formatting
+pub fn remove(path: &Path, contents: &str, keys: &[Vec<String>]) -> Result<String, MergeError> {
+    let mut expected = parse(Format::Yaml, path, contents)?;
+    let mut lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+    let unit = indent_unit(&lines);
+
+    for key in keys {
+        let (start, indent) = root(path, &lines)?;
+        let range = (start, lines.len(), indent);
+
+        unset(path, &mut lines, range, unit, key, &expected)?;
+        unset_value(&mut expected, key);
+    }
+
+    finish(path, lines, &expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(path: &str) -> Vec<String> {
+        path.split('.').map(str::to_owned).collect()
+    }
+
+    fn merge_at(contents: &str, path: &str, value: Value) -> String {
+        merge(Path::new("test.yml"), contents, &[(key(path), &value)]).unwrap()
+    }
+
+    fn remove_all(contents: &str, paths: &[&str]) -> String {
+        let keys: Vec<Vec<String>> = paths.iter().map(|path| key(path)).collect();
+        remove(Path::new("test.yml"), contents, &keys).unwrap()
+    }
+
+    #[test]
+    fn merge_replaces_a_value_keeping_comments() {
+        assert_eq!(
+            merge_at("# font\nsize: 10 # points\nfont: mono\n", "size", json!(14)),
+            "# font\nsize: 14 # points\nfont: mono\n"
+        );
+    }
+
+    #[test]
+    fn merge_keeps_indented_sequences() {
+        assert_eq!(
+            merge_at("imports:\n  - a.yml\n", "font.size", json!(12)),
+            "imports:\n  - a.yml\nfont:\n  size: 12\n"
+        );
+    }
+
+    #[test]
+    fn merge_adds_keys_to_nested_mappings() {
+        assert_eq!(
+            merge_at(
+                "font:\n    normal:\n        family: mono\n",
+                "font.normal.style",
+                json!("Bold")
+            ),
+            "font:\n    normal:\n        family: mono\n        style: Bold\n"
+        );
+    }
+
+    #[test]
+    fn merge_into_flow_mappings() {
+        assert_eq!(
+            merge_at("font: {family: mono}\n", "font.size", json!(12)),
+            "font:\n  family: mono\n  size: 12\n"
+        );
+    }
+
+    #[test]
+    fn merge_into_an_empty_file() {
+        assert_eq!(
+            merge_at("", "a.b", json!([1, 2])),
+            "a:\n  b:\n  - 1\n  - 2\n"
+        );
+    }
+
+    #[test]
+    fn merge_rejects_several_documents() {
+        let value = json!(1);
+        let result = merge(
+            Path::new("test.yml"),
+            "a: 1\n---\nb: 2\n",
+            &[(key("c"), &value)],
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn remove_prunes_mappings_left_empty() {
+        assert_eq!(
+            remove_all(
+                "a: 1\nfont:\n  # the size\n  size: 12\nb: 2\n",
+                &["font.size"]
+            ),
+            "a: 1\nb: 2\n"
+        );
+    }
+
+    #[test]
+    fn remove_keeps_mappings_with_other_keys() {
+        assert_eq!(
+            remove_all("font:\n  size: 12 # big\n  family: mono\n", &["font.size"]),
+            "font:\n  family: mono\n"
+        );
+    }
+
+    #[test]
+    fn remove_a_value_spanning_lines() {
+        assert_eq!(
+            remove_all(
+                "imports:\n- a.yml\n- b.yml\ntext: |\n  one\n  two\nc: 3\n",
+                &["imports", "text"]
+            ),
+            "c: 3\n"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use crate::config::Config;
+use crate::errors::{CrateError, MergeError};
+use crate::merge::{self, Format};
+use crate::walk::absolute;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The value a key of a structured file had before a setting first set it, as JSON
+#[derive(Deserialize, Serialize)]
+struct MergedKey {
+    key: Vec<String>,
+    /// `None` if the key wasn't there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
+
+/// A structured file settings merge into, with the keys they set in the order they were first set
+#[derive(Deserialize, Serialize)]
+struct MergedFile {
+    format: Format,
+    existed: bool,
+    #[serde(default)]
+    keys: Vec<MergedKey>,
+}
+
+/// Index of every file rconfigure has overwritten and whether it existed beforehand, along with
+/// the keys it set in structured files
+#[derive(Deserialize, Serialize, Default)]
+struct Originals {
+    #[serde(default)]
+    files: HashMap<PathBuf, bool>,
+    #[serde(default)]
+    merged: HashMap<PathBuf, MergedFile>,
+}
+
+fn read_index(config: &Config) -> Result<Originals, CrateError> {
+    let index = config.originals_file();
+
+    match fs::read_to_string(&index) {
+        Ok(s) => Ok(toml::from_str(s.as_str())?),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Originals::default()),
+        Err(err) => Err(CrateError::ReadFailure(index, err)),
+    }
+}
+
+fn write_index(config: &Config, originals: &Originals) -> Result<(), CrateError> {
+    let index = config.originals_file();
+
+    fs::write(&index, toml::to_string(originals)?)
+        .map_err(|err| CrateError::WriteFailure(index, err))
+}
+
+/// Gets the location inside the store that mirrors an absolute path
+fn stored_path(store: &Path, path: &Path) -> PathBuf {
+    store.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// Saves the contents of a file before rconfigure overwrites it for the first time
+pub fn save<P: AsRef<Path>>(config: &Config, path: P) -> Result<(), CrateError> {
+    let path = absolute(path)?;
+    let mut originals = read_index(config)?;
+
+    // only the state from before rconfigure first touched the file is kept
+    if originals.files.contains_key(&path) {
+        return Ok(());
+    }
+
+    let existed = path.is_file();
+
+    if existed {
+        let stored = stored_path(&config.originals_dir(), &path);
+
+        if let Some(parent) = stored.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| CrateError::WriteFailure(parent.to_owned(), err))?;
+        }
+
+        fs::copy(&path, &stored).map_err(|err| CrateError::WriteFailure(stored, err))?;
+    }
+
+    originals.files.insert(path, existed);
+    write_index(config, &originals)?;
+
+    Ok(())
+}
+
+/// Saves the values keys of a structured file have before settings first set them
+pub fn save_keys<P: AsRef<Path>>(
+    config: &Config,
+    path: P,
+    format: Format,
+    keys: &[Vec<String>],
+) -> Result<(), CrateError> {
+    let path = absolute(path)?;
+    let mut originals = read_index(config)?;
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => Some(contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(CrateError::ReadFailure(path, err)),
+    };
+
+    let current = merge::parse(format, &path, contents.as_deref().unwrap_or_default())?;
+
+    let file = originals.merged.entry(path).or_insert_with(|| MergedFile {
+        format,
+        existed: contents.is_some(),
+        keys: Vec::new(),
+    });
+
+    for key in keys {
+        // only the value from before rconfigure first set the key is kept
+        if file.keys.iter().any(|k| k.key == *key) {
+            continue;
+        }
+
+        file.keys.push(MergedKey {
+            key: key.to_owned(),
+            value: merge::get(&current, key).map(Value::to_string),
+        });
+    }
+
+    write_index(config, &originals)?;
+
+    Ok(())
+}
+
+/// What a structured file held before settings first merged into it
+pub struct Merged {
+    /// Whether the file existed before rconfigure created it
+    pub existed: bool,
+    /// The original value of every key settings set, `None` for keys that weren't there, in the
+    /// order they were first set
+    pub keys: Vec<(Vec<String>, Option<Value>)>,
+}
+
+fn merged(config: &Config, file: &MergedFile) -> Result<Merged, CrateError> {
+    let mut keys = Vec::new();
+
+    for original in &file.keys {
+        let value = match &original.value {
+            Some(value) => Some(serde_json::from_str(value).map_err(|err| {
+                MergeError::ErrorParsingTarget(config.originals_file(), Box::new(err))
+            })?),
+            None => None,
+        };
+
+        keys.push((original.key.to_owned(), value));
+    }
+
+    Ok(Merged {
+        existed: file.existed,
+        keys,
+    })
+}
+
+/// Reads what the keys settings set in a structured file were before they first set them, `None`
+/// if no setting merged into it
+pub fn read_merged<P: AsRef<Path>>(config: &Config, path: P) -> Result<Option<Merged>, CrateError> {
+    let path = absolute(path)?;
+
+    match read_index(config)?.merged.get(&path) {
+        Some(file) => Ok(Some(merged(config, file)?)),
+        None => Ok(None),
+    }
+}
+
+/// Forgets the original values of keys of a structured file that have been put back
+pub fn forget_keys<P: AsRef<Path>>(
+    config: &Config,
+    path: P,
+    keys: &[Vec<String>],
+) -> Result<(), CrateError> {
+    let path = absolute(path)?;
+    let mut originals = read_index(config)?;
+
+    if let Some(file) = originals.merged.get_mut(&path) {
+        file.keys.retain(|original| !keys.contains(&original.key));
+
+        if file.keys.is_empty() {
+            originals.merged.remove(&path);
+        }
+    }
+
+    write_index(config, &originals)?;
+
+    Ok(())
+}
+
+/// Puts back the original values of the keys settings set in a structured file, removing it if
+/// rconfigure created it and nothing else is left in it
+fn restore_keys(path: &Path, format: Format, merged: &Merged) -> Result<(), CrateError> {
+    let current = match fs::read_to_string(path) {
+        Ok(current) => current,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(CrateError::ReadFailure(path.to_owned(), err)),
+    };
+
+    // the values recorded first are the oldest so they are put back last
+    let keys: Vec<_> = merged.keys.iter().rev().cloned().collect();
+    let contents = merge::restore(format, path, &current, &keys)?;
+
+    let result = if !merged.existed && merge::parse(format, path, &contents)?.is_empty() {
+        fs::remove_file(path)
+    } else {
+        fs::write(path, contents)
+    };
+
+    result.map_err(|err| CrateError::WriteFailure(path.to_owned(), err))
+}
+
+/// Puts back the original contents of a file, or removes it if rconfigure created it
+fn restore_file(store: &Path, path: &Path, existed: bool) -> Result<(), CrateError> {
+    let result = if existed {
+        fs::copy(stored_path(store, path), path).map(drop)
+    } else if path.is_file() {
+        // the file was created by rconfigure so it gets removed
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    };
+
+    result.map_err(|err| CrateError::WriteFailure(path.to_owned(), err))?;
+
+    Ok(())
+}
+
+/// What a file contained before rconfigure first overwrote it
+pub enum Original {
+    /// rconfigure created the file
+    Missing,
+    Contents(Vec<u8>),
+}
+
+/// Reads what a file contained before rconfigure first overwrote it, `None` if it never did
+pub fn read<P: AsRef<Path>>(config: &Config, path: P) -> Result<Option<Original>, CrateError> {
+    let path = absolute(path)?;
+
+    match read_index(config)?.files.get(&path) {
+        Some(true) => {
+            let stored = stored_path(&config.originals_dir(), &path);
+            let contents = fs::read(&stored).map_err(|err| CrateError::ReadFailure(stored, err))?;
+            Ok(Some(Original::Contents(contents)))
+        }
+
+        Some(false) => Ok(Some(Original::Missing)),
+        None => Ok(None),
+    }
+}
+
+/// Forgets the original contents of files that have been put back
+pub fn forget(config: &Config, paths: &[PathBuf]) -> Result<(), CrateError> {
+    let store = config.originals_dir();
+    let mut originals = read_index(config)?;
+
+    for path in paths {
+        let path = absolute(path)?;
+
+        if originals.files.remove(&path).is_some() {
+            let stored = stored_path(&store, &path);
+
+            if stored.is_file() {
+                fs::remove_file(&stored).map_err(|err| CrateError::WriteFailure(stored, err))?;
+            }
+        }
+    }
+
+    write_index(config, &originals)?;
+
+    Ok(())
+}
+
+/// Restores every overwritten file to its original contents and clears the store
+pub fn restore_all(config: &Config) -> Result<(), CrateError> {
+    let store = config.originals_dir();
+    let originals = read_index(config)?;
+
+    for (path, existed) in &originals.files {
+        restore_file(&store, path, *existed)?;
+    }
+
+    // structured files only get the keys settings set put back
+    for (path, file) in &originals.merged {
+        restore_keys(path, file.format, &merged(config, file)?)?;
+    }
+
+    if store.is_dir() {
+        fs::remove_dir_all(&store).map_err(|err| CrateError::WriteFailure(store, err))?;
+    }
+
+    let index = config.originals_file();
+
+    if index.is_file() {
+        fs::remove_file(&index).map_err(|err| CrateError::WriteFailure(index, err))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use rconfigure::CrateError;
+use std::fmt;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::path::PathBuf;
+
+/// A profile or setting to pick, shown by its name and path
+pub struct Item {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.path.display())
+    }
+}
+
+/// Checks whether every character of the pattern appears in the text in order, ignoring case
+fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|p| text.any(|t| t == p))
+}
+
+/// Asks for a filter and keeps the items fuzzily matching it
+fn filter(items: Vec<Item>) -> Result<Vec<Item>, CrateError> {
+    if !stdin().is_terminal() {
+        return Err(CrateError::NotInteractive);
+    }
+
+    print!("filter (leave empty to show everything): ");
+    stdout().flush().map_err(CrateError::TerminalFailure)?;
+
+    let mut pattern = String::new();
+    stdin()
+        .read_line(&mut pattern)
+        .map_err(CrateError::TerminalFailure)?;
+
+    let items: Vec<Item> = items
+        .into_iter()
+        .filter(|item| fuzzy_match(&pattern, &item.to_string()))
+        .collect();
+
+    if items.is_empty() {
+        println!("nothing matches {:?}", pattern.trim());
+    }
+
+    Ok(items)
+}
+
+/// Lets the user pick a single item, `None` if nothing was picked
+pub fn one(prompt: &str, items: Vec<Item>) -> Result<Option<PathBuf>, CrateError> {
+    let items = filter(items)?;
+
+    if items.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(quiz::select(prompt, &items).map(|item| item.path.to_owned()))
+}
+
+/// Lets the user pick any number of items
+pub fn many(prompt: &str, items: Vec<Item>) -> Result<Vec<PathBuf>, CrateError> {
+    let items = filter(items)?;
+
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(quiz::checkbox(prompt, &items)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, checked)| *checked)
+        .map(|(item, _)| item.path.to_owned())
+        .collect())
+}
@@ -1,25 +1,80 @@
+use crate::errors::CrateError;
 use crate::profile::Profile;
+use crate::state::State;
+use crate::transaction::{self, FileWrite};
+use crate::{backup, originals};
 use rhai::Engine;
+use std::fs;
 
 impl Profile {
-    pub fn apply(&self, engine: &Engine) {
+    /// Renders every output of the profile into the writes applying it makes and the state
+    /// recording them without changing any file, starting from writes that are already pending
+    pub(crate) fn plan(
+        &self,
+        engine: &Engine,
+        pending: Vec<FileWrite>,
+    ) -> Result<(Vec<FileWrite>, State), CrateError> {
         // check for setting conflicts
-        if let Some((setting1, setting2, target)) = self.setting_conflict(None) {
-            println!("failed to apply profile, found setting conflict!");
-            println!(
-                "settings {:?} and {:?} both set values for target {:?}",
-                setting1.name(),
-                setting2.name(),
-                target
-            );
-            std::process::exit(1);
+        self.check_conflicts()?;
+
+        let mut outputs = pending;
+        let mut state = State::new(&self.path);
+
+        for (rendered, settings) in self.render(engine)? {
+            // managed blocks of several settings can end up in the same file
+            let index = outputs.iter().position(|o| o.path == rendered.path);
+            let current = match index {
+                Some(i) => Some(outputs[i].contents.to_owned()),
+                None => fs::read(&rendered.path).ok(),
+            };
+
+            let contents = rendered.output(current.as_deref())?;
+            state.record(&rendered, &settings, &contents);
+
+            let output = FileWrite {
+                contents,
+                mode: rendered.header.mode(),
+                create_dirs: rendered.header.create_dirs(),
+                encoding: rendered.header.encoding(),
+                path: rendered.path,
+            };
+
+            match index {
+                Some(i) => outputs[i] = output,
+                None => outputs.push(output),
+            }
         }
 
-        // go through each setting and apply it
-        for setting in &self.settings {
-            setting.apply(engine);
+        Ok((outputs, state))
+    }
+
+    /// Saves the original contents of every file the state records rconfigure fully owning
+    pub(crate) fn save_originals(&self, state: &State) -> Result<(), CrateError> {
+        // only files rconfigure fully owns get restored by unset, blocks are removed and structured
+        // files get back the values of the keys settings set
+        for (path, output) in state.outputs() {
+            let format = self.settings.iter().find_map(|s| s.merge_format(path));
+
+            if let Some(format) = format {
+                let keys: Vec<Vec<String>> = self
+                    .settings
+                    .iter()
+                    .flat_map(|s| s.merge_keys())
+                    .filter(|(merge, _)| merge == path)
+                    .map(|(_, key)| key)
+                    .collect();
+
+                originals::save_keys(&self.config, path, format, &keys)?;
+            } else if !output.block() {
+                originals::save(&self.config, path)?;
+            }
         }
 
+        Ok(())
+    }
+
+    /// Runs the hooks of every setting and then the ones of the profile
+    pub(crate) fn run_hooks(&self) {
         for setting in &self.settings {
             for hook in setting.hooks() {
                 hook.run();
@@ -30,4 +85,19 @@ impl Profile {
             hook.run();
         }
     }
+
+    /// Applies every setting of the profile and runs the hooks
+    pub fn apply(&self, engine: &Engine) -> Result<(), CrateError> {
+        // render every setting before writing anything so a template error leaves all files as is
+        let (outputs, state) = self.plan(engine, Vec::new())?;
+
+        self.save_originals(&state)?;
+        backup::create(&self.config, &outputs)?;
+        transaction::write_all(&outputs)?;
+        state.save(&self.config)?;
+
+        self.run_hooks();
+
+        Ok(())
+    }
 }
@@ -0,0 +1,44 @@
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use rhai::Engine;
+use std::fs;
+use std::path::PathBuf;
+
+/// The contents of a file on disk along with what applying a profile would change them to
+#[derive(Clone, Debug)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    /// The contents on disk, `None` if the file does not exist yet
+    pub current: Option<String>,
+    pub contents: String,
+}
+
+impl Profile {
+    /// Renders every setting of the profile and compares it against the files on disk, leaving
+    /// out the files that would not change
+    pub fn diff(&self, engine: &Engine) -> Result<Vec<FileDiff>, CrateError> {
+        // outputs to the same file are chained the way applying writes them, so each file gets a
+        // single diff
+        let (outputs, _) = self.plan(engine, Vec::new())?;
+        let mut diffs = Vec::new();
+
+        for output in outputs {
+            let current = fs::read(&output.path)
+                .ok()
+                .map(|c| output.encoding.decode(&c));
+            let contents = output.encoding.decode(&output.contents);
+
+            if current.as_deref().unwrap_or_default() == contents {
+                continue;
+            }
+
+            diffs.push(FileDiff {
+                path: output.path,
+                current,
+                contents,
+            });
+        }
+
+        Ok(diffs)
+    }
+}
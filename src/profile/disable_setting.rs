@@ -1,54 +1,184 @@
-use crate::errors::CrateError;
-use crate::profile::{Profile, ProfileDeserialized, ProfileTable};
-use crate::{errors::ProfileError, setting};
-use dirs::config_dir;
-use std::{fs, path::Path};
+use crate::errors::{CrateError, ProfileError};
+use crate::originals::{self, Original};
+use crate::profile::dry_run::{self, FileChange};
+use crate::profile::Profile;
+use crate::setting::{self, Setting};
+use crate::state::State;
+use crate::transaction::{self, FileWrite};
+use crate::{backup, block, merge};
+use rhai::Engine;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// How to undo the outputs of a setting
+#[derive(Default)]
+struct Undo {
+    writes: Vec<FileWrite>,
+    removals: Vec<PathBuf>,
+    /// Files put back to their original contents
+    restored: Vec<PathBuf>,
+    /// Keys of structured files put back to their original values
+    keys: Vec<(PathBuf, Vec<Vec<String>>)>,
+}
 
 impl Profile {
-    pub fn disable_setting<P: AsRef<Path>>(&mut self, path: P) -> Result<(), CrateError> {
-        let setting = setting::parse(&path);
-        self.settings.retain(|s| s.path() != setting.path());
+    /// Works out how to undo every output a setting generated when this profile was last applied,
+    /// putting back the files only the setting generated and taking it out of the ones other
+    /// settings contributed to
+    fn undo(&self, setting: &Setting) -> Result<Undo, CrateError> {
+        let state = State::load(&self.config)?;
+        let mut undo = Undo::default();
+
+        if state.profile() == Some(self.path.as_path()) {
+            for (output, record) in state.outputs() {
+                if !record.settings().contains(&setting.path()) {
+                    continue;
+                }
+
+                let sole = record.settings() == [setting.path()];
+
+                if let Some(format) = setting.merge_format(output) {
+                    // only the keys the setting set are put back in a structured file
+                    let keys: Vec<Vec<String>> = setting
+                        .merge_keys()
+                        .into_iter()
+                        .filter(|(path, _)| path == output)
+                        .map(|(_, key)| key)
+                        .collect();
+
+                    let current = match fs::read_to_string(output) {
+                        Ok(current) => current,
+                        Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                        Err(err) => return Err(CrateError::ReadFailure(output.to_owned(), err)),
+                    };
+
+                    let merged = originals::read_merged(&self.config, output)?;
+                    let recorded = merged.as_ref().map_or(&[][..], |m| &m.keys[..]);
 
-        let path = if path.as_ref().is_absolute() {
-            path.as_ref().to_owned()
-        } else {
-            config_dir()
-                .ok_or_else(|| CrateError::NoConfigDir)?
-                .join("rconfigure/profiles")
-                .join(path)
-        };
-
-        // FIXME: maybe add a specific thing for file not found
-        let s = fs::read_to_string(&self.path)
-            .map_err(|err| ProfileError::ErrorReadingProfile(self.path.to_owned(), err))?;
-
-        let mut profile: ProfileDeserialized =
-            toml::from_str(s.as_str())?; // .map_err(|err| -> ProfileError { err.into() })?;
-
-        if let Some(ProfileTable {
-            settings: Some(ref mut settings),
-            ..
-        }) = profile.profile_table
-        {
-            let mut settings_buf = vec![];
-
-            for (string, contained_setting) in settings
-                .iter()
-                .map(|string| (string, setting::parse(string)))
-            {
-                if setting.path() != contained_setting.path() {
-                    settings_buf.push(string.to_owned())
+                    // keys with no recorded value weren't there before the setting set them, and
+                    // the values recorded first are the oldest so they are put back last
+                    let mut values: Vec<_> = keys
+                        .iter()
+                        .filter(|key| !recorded.iter().any(|(k, _)| k == *key))
+                        .map(|key| (key.to_owned(), None))
+                        .collect();
+
+                    values.extend(
+                        recorded
+                            .iter()
+                            .rev()
+                            .filter(|(key, _)| keys.contains(key))
+                            .cloned(),
+                    );
+
+                    let contents = merge::restore(format, output, &current, &values)?;
+
+                    // a file created for the setting alone goes away once nothing is left in it
+                    let created = merged.is_some_and(|m| !m.existed);
+
+                    if sole && created && merge::parse(format, output, &contents)?.is_empty() {
+                        undo.removals.push(output.to_owned());
+                    } else {
+                        undo.writes
+                            .push(FileWrite::new(output, contents.into_bytes()));
+                    }
+
+                    if sole {
+                        undo.restored.push(output.to_owned());
+                    }
+
+                    undo.keys.push((output.to_owned(), keys));
+                } else if record.block() {
+                    let id = setting.id();
+
+                    if let Some(contents) =
+                        block::removed_from_file(output, Some(&id), record.comment())?
+                    {
+                        undo.writes
+                            .push(FileWrite::new(output, contents.into_bytes()));
+                    }
+                } else if sole {
+                    match originals::read(&self.config, output)? {
+                        Some(Original::Contents(contents)) => {
+                            undo.writes.push(FileWrite::new(output, contents))
+                        }
+                        Some(Original::Missing) => undo.removals.push(output.to_owned()),
+                        None => (),
+                    }
+
+                    undo.restored.push(output.to_owned());
                 }
             }
+        }
 
-            *settings = settings_buf;
+        Ok(undo)
+    }
+
+    /// Removes a setting from the profile, undoing every file it generated and re-applying the
+    /// remaining settings, giving what that does to each file
+    pub fn disable_setting<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        engine: &Engine,
+        dry_run: bool,
+    ) -> Result<Vec<(PathBuf, FileChange)>, CrateError> {
+        let setting = setting::parse(&self.config, &path)?;
+        let enabled = self.settings.len();
+        self.settings.retain(|s| s.path() != setting.path());
+
+        if self.settings.len() == enabled {
+            return Err(ProfileError::SettingNotEnabled(setting.path()).into());
+        }
+
+        let Undo {
+            writes,
+            mut removals,
+            mut restored,
+            keys,
+        } = self.undo(&setting)?;
+
+        // render the remaining settings on top of the undone files before changing anything, so
+        // a template error leaves every file and the profile as they were
+        let (outputs, state) = self.plan(engine, writes)?;
+
+        // files rendered again by another template stay managed
+        removals.retain(|path| !outputs.iter().any(|o| o.path == *path));
+        restored.retain(|path| !state.outputs().contains_key(path));
+
+        let changes = dry_run::changes(&outputs, &removals, &restored);
+
+        // a dry run only previews the profile without the setting so nothing is touched
+        if dry_run {
+            return Ok(changes);
         }
 
-        let contents = toml::to_string(&profile)?; // .map_err(|err| -> ProfileError { err.into() })?;
+        // the profile file is written along with the outputs so neither changes without the other
+        let profile = FileWrite::new(&self.path, self.settings_document()?.into_bytes());
+
+        self.save_originals(&state)?;
+        backup::create(&self.config, &outputs)?;
+        transaction::commit(&[outputs, vec![profile]].concat(), &removals)?;
+
+        originals::forget(&self.config, &restored)?;
 
-        fs::write(&self.path, contents)
-            .map_err(|err| ProfileError::ErrorWritingProfile(self.path.to_owned(), err))?;
+        // keys another setting still sets keep their original values for when it is disabled
+        let remaining: Vec<(PathBuf, Vec<String>)> =
+            self.settings.iter().flat_map(|s| s.merge_keys()).collect();
+
+        for (path, mut keys) in keys {
+            keys.retain(|key| !remaining.iter().any(|(p, k)| *p == path && k == key));
+            originals::forget_keys(&self.config, &path, &keys)?;
+        }
+
+        state.save(&self.config)?;
+
+        self.run_hooks();
+
+        for hook in setting.disable_hooks() {
+            hook.run();
+        }
 
-        Ok(())
+        Ok(changes)
     }
 }
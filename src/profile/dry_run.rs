@@ -0,0 +1,70 @@
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::transaction::FileWrite;
+use rhai::Engine;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// What applying a profile would do to a single file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileChange {
+    LeftAlone,
+    Changed,
+    Created,
+    /// Put back to what it was before rconfigure first wrote it
+    Restored,
+    Removed,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            FileChange::LeftAlone => "left alone",
+            FileChange::Changed => "changed",
+            FileChange::Created => "created",
+            FileChange::Restored => "restored",
+            FileChange::Removed => "removed",
+        })
+    }
+}
+
+/// Works out what writing the outputs and removing the removals would do to each file, where the
+/// restored files are put back to their original contents
+pub(crate) fn changes(
+    outputs: &[FileWrite],
+    removals: &[PathBuf],
+    restored: &[PathBuf],
+) -> Vec<(PathBuf, FileChange)> {
+    let mut changes = Vec::new();
+
+    for output in outputs {
+        let change = match fs::read(&output.path) {
+            Ok(current) if current == output.contents => FileChange::LeftAlone,
+            Ok(_) if restored.contains(&output.path) => FileChange::Restored,
+            Ok(_) => FileChange::Changed,
+            Err(_) => FileChange::Created,
+        };
+
+        changes.push((output.path.to_owned(), change));
+    }
+
+    for path in removals {
+        if path.exists() {
+            changes.push((path.to_owned(), FileChange::Removed));
+        }
+    }
+
+    changes
+}
+
+impl Profile {
+    /// Renders every setting of the profile and works out what applying it would do to each file
+    /// without writing anything or running any hooks
+    pub fn dry_run(&self, engine: &Engine) -> Result<Vec<(PathBuf, FileChange)>, CrateError> {
+        // outputs to the same file are chained the way applying writes them
+        let (outputs, _) = self.plan(engine, Vec::new())?;
+
+        Ok(changes(&outputs, &[], &[]))
+    }
+}
@@ -1,78 +1,95 @@
-use crate::profile::{Profile, ProfileDeserialized, ProfileTable};
-use crate::setting;
-use dirs::config_dir;
-use std::{fs, path::Path};
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::setting::{self, Setting};
+use std::path::{Path, PathBuf};
+
+/// What enabling a setting did to the profile
+#[derive(Clone, Debug)]
+pub enum Enabled {
+    /// The setting was already enabled so nothing changed
+    AlreadyEnabled,
+    /// Replacing a conflicting setting was declined so the profile was left as it was
+    Declined,
+    /// The setting was enabled, overriding keys of other settings, listed as the overridden
+    /// setting, the setting overriding it, the target and the key
+    Enabled(Vec<(String, String, PathBuf, String)>),
+}
 
 impl Profile {
-    pub fn enable_setting<P: AsRef<Path>>(&mut self, path: P, noconfirm: bool) {
-        let setting = setting::parse(&path);
+    /// Adds a setting to the profile, resolving any conflicts it has with enabled settings in a
+    /// strict profile by asking `replace` whether the enabled setting, given along with the new
+    /// setting, the target and the key, should be replaced
+    pub fn enable_setting<P, F>(
+        &mut self,
+        path: P,
+        mut replace: F,
+        dry_run: bool,
+    ) -> Result<Enabled, CrateError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Setting, &Setting, &Path, &str) -> Result<bool, CrateError>,
+    {
+        let setting = setting::parse(&self.config, &path)?;
+        let previous_settings = self.settings.clone();
 
-        // resolve all setting conflicts
-        while let Some((setting1, setting2, target)) = self.setting_conflict(Some(&setting)) {
-            if noconfirm {
-                // retain everything that is NOT setting1
-                self.settings.retain(|s| s.path() != setting1.path());
-            } else {
-                println!("failed to apply profile, found setting conflict!");
+        if self.settings.iter().any(|s| s.path() == setting.path()) {
+            return Ok(Enabled::AlreadyEnabled);
+        }
 
-                let prompt = format!(
-                "settings {0:?} and {1:?} both set values for target {2:?}, replace setting {0:?} with {1:?}?",
-                setting1.name(),
-                setting2.name(),
-                target
-            );
+        // settings override each other's keys unless the profile is strict
+        let mut overrides = Vec::new();
 
-                if quiz::confirm(&prompt) {
-                    // retain everything that is NOT setting1
-                    self.settings.retain(|s| s.path() != setting1.path());
-                } else {
-                    std::process::exit(0);
+        if !self.strict {
+            for (overridden, overriding, target, key) in self.conflicts(Some(&setting)) {
+                if overridden.path() == setting.path() || overriding.path() == setting.path() {
+                    overrides.push((overridden.name(), overriding.name(), target, key));
                 }
             }
         }
 
-        // after resolving setting conflicts push the setting to be added
-        self.settings.push(setting.clone());
+        // conflicts between settings that were already enabled can't be resolved by replacing one
+        // of them with the new setting
+        self.check_conflicts()?;
 
-        let path = if path.as_ref().is_absolute() {
-            path.as_ref().to_owned()
-        } else {
-            // FIXME: better error handling
-            config_dir()
-                .expect("config dir borked")
-                .join("rconfigure/profiles")
-                .join(path)
-        };
+        // resolve the setting conflicts of a strict profile that involve the new setting
+        while self.strict {
+            let conflict = self.conflicts(Some(&setting)).into_iter().find_map(
+                |(setting1, setting2, target, key)| {
+                    // the enabled setting to replace, which may come second when it has a higher
+                    // priority
+                    if setting1.path() == setting.path() {
+                        Some((setting2.to_owned(), target, key))
+                    } else if setting2.path() == setting.path() {
+                        Some((setting1.to_owned(), target, key))
+                    } else {
+                        None
+                    }
+                },
+            );
+
+            let (replaced, target, key) = match conflict {
+                Some(conflict) => conflict,
+                None => break,
+            };
 
-        // FIXME: handle errors for file not found
-        let s = fs::read_to_string(&self.path).unwrap();
-        let mut profile: ProfileDeserialized = match toml::from_str(s.as_str()) {
-            Ok(value) => value,
-            Err(e) => {
-                println!("error when parsing setting {:?}", path);
-                println!("{}", e);
-                std::process::exit(1);
+            if replace(&replaced, &setting, &target, &key)? {
+                // retain everything that is NOT the replaced setting
+                self.settings.retain(|s| s.path() != replaced.path());
+            } else {
+                // leave the profile as it was before trying to enable the setting
+                self.settings = previous_settings;
+                return Ok(Enabled::Declined);
             }
-        };
+        }
+
+        // after resolving setting conflicts push the setting to be added
+        self.settings.push(setting);
 
-        if let Some(ProfileTable {
-            settings: Some(ref mut settings),
-            ..
-        }) = profile.profile_table
-        {
-            *settings = self
-                .settings
-                .iter()
-                // FIXME: check if the path is relative to the settings directory and use short names if possible
-                .map(|s| s.path().to_str().unwrap().to_string())
-                .collect();
+        // a dry run only previews the setting so the profile is left untouched
+        if !dry_run {
+            self.write_settings()?;
         }
 
-        // serialize and write to file
-        fs::write(
-            &self.path,
-            toml::to_string(&profile).expect("failed to serialize profile"),
-        )
-        .unwrap();
+        Ok(Enabled::Enabled(overrides))
     }
 }
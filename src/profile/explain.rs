@@ -0,0 +1,92 @@
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::script::ScriptValue;
+use crate::setting::{Origin, TargetValue};
+use crate::template;
+use rhai::Engine;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// The final value of a template variable and where it came from
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    pub value: ScriptValue,
+    /// The setting that provided the value, `None` for a default declared by the template
+    pub setting: Option<PathBuf>,
+    /// Where in the setting the value was found
+    pub origin: Option<Origin>,
+    /// The rhai script that produced the value
+    pub script: Option<PathBuf>,
+    /// The settings whose values for the same key were overridden, earliest first
+    pub overrides: Vec<PathBuf>,
+}
+
+impl Profile {
+    /// Works out the value of every variable a template gets rendered with and where it came from
+    pub fn explain<P: AsRef<Path>>(
+        &self,
+        template: P,
+        engine: &Engine,
+    ) -> Result<BTreeMap<String, Explanation>, CrateError> {
+        let template = if template.as_ref().is_absolute() {
+            template.as_ref().to_owned()
+        } else {
+            self.config.templates_dir().join(template)
+        };
+
+        let mut explanations: BTreeMap<String, Explanation> = BTreeMap::new();
+
+        for setting in self.layered(None) {
+            if !setting.targets().contains(&template) {
+                continue;
+            }
+
+            for (k, (v, origin)) in setting.compose_origins(&template) {
+                let script = match &v {
+                    TargetValue::Script { script, .. } => Some(setting.script_path(script)),
+                    _ => None,
+                };
+
+                // a script produces its own keys rather than the one it is declared under
+                for (k, value) in setting.values(HashMap::from([(k, v)]), engine)? {
+                    let overrides = match explanations.remove(&k) {
+                        Some(Explanation {
+                            setting: Some(previous),
+                            mut overrides,
+                            ..
+                        }) => {
+                            overrides.push(previous);
+                            overrides
+                        }
+                        _ => Vec::new(),
+                    };
+
+                    let explanation = Explanation {
+                        value,
+                        setting: Some(setting.path()),
+                        origin: Some(origin.to_owned()),
+                        script: script.to_owned(),
+                        overrides,
+                    };
+
+                    explanations.insert(k, explanation);
+                }
+            }
+        }
+
+        // variables declared by the template fall back to their defaults
+        let (header, _) = template::parse(&template)?;
+
+        for (k, v) in header.variables() {
+            explanations.entry(k.to_owned()).or_insert(Explanation {
+                value: v.to_owned(),
+                setting: None,
+                origin: None,
+                script: None,
+                overrides: Vec::new(),
+            });
+        }
+
+        Ok(explanations)
+    }
+}
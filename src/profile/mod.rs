@@ -1,84 +1,276 @@
 mod apply;
+mod diff;
 mod disable_setting;
+mod dry_run;
 mod enable_setting;
+mod explain;
+mod render;
 mod setting_conflict;
+mod status;
+mod unset;
+mod write_settings;
 
+pub use diff::FileDiff;
+pub use dry_run::FileChange;
+pub use enable_setting::Enabled;
+pub use explain::Explanation;
+pub(crate) use unset::restore;
+
+use crate::config::Config;
+use crate::errors::{CrateError, ProfileError};
 use crate::hook::Hook;
-use crate::setting::{self, Setting};
-use dirs::config_dir;
-use serde::{Deserialize, Serialize};
+use crate::setting::{self, Setting, TargetValue};
+use crate::walk;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize)]
 struct ProfileDeserialized {
     #[serde(rename = "profile")]
     profile_table: Option<ProfileTable>,
 }
 
-#[derive(Deserialize, Serialize)]
+/// A setting listed in a profile, either by path alone or along with values to layer on top of
+/// its own
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum SettingEntry {
+    Path(String),
+    Inline {
+        setting: String,
+        #[serde(flatten)]
+        params: HashMap<String, TargetValue>,
+    },
+}
+
+impl SettingEntry {
+    /// Get the path of the setting as written in the profile
+    fn path(&self) -> &str {
+        match self {
+            SettingEntry::Path(path) => path,
+            SettingEntry::Inline { setting, .. } => setting,
+        }
+    }
+}
+
+/// The fields a `[profile]` table can have
+pub(crate) const FIELDS: &[&str] = &[
+    "name",
+    "strict",
+    "extends",
+    "settings",
+    "remove_settings",
+    "hooks",
+    "unset_hooks",
+];
+
+#[derive(Deserialize)]
 struct ProfileTable {
     name: Option<String>,
-    settings: Option<Vec<String>>,
+    strict: Option<bool>,
+    extends: Option<Vec<String>>,
+    settings: Option<Vec<SettingEntry>>,
+    remove_settings: Option<Vec<String>>,
     #[serde(default)]
     hooks: Vec<Hook>,
+    #[serde(default)]
+    unset_hooks: Vec<Hook>,
+}
+
+/// The hooks a single profile of an inheritance chain lists itself
+struct HookSource {
+    profile: PathBuf,
+    hooks: Vec<Hook>,
+    unset_hooks: Vec<Hook>,
 }
 
 pub struct Profile {
+    config: Config,
     path: PathBuf,
     name: String,
+    strict: bool,
+    extends: Vec<PathBuf>,
     settings: Vec<Setting>,
+    inherited: Vec<PathBuf>,
     hooks: Vec<Hook>,
+    unset_hooks: Vec<Hook>,
+    hook_sources: Vec<HookSource>,
 }
 
-pub fn parse<P: AsRef<Path>>(path: P) -> Profile {
-    let path = if path.as_ref().is_absolute() {
-        path.as_ref().to_owned()
+impl Profile {
+    /// Get the path of the profile file
+    pub fn path(&self) -> PathBuf {
+        self.path.to_owned()
+    }
+
+    /// Get the name of the profile
+    pub fn name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    /// Get whether settings setting the same key are an error instead of overriding each other
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Get the paths of the profiles this one extends
+    pub fn extends(&self) -> &Vec<PathBuf> {
+        &self.extends
+    }
+
+    /// Get the enabled settings
+    pub fn settings(&self) -> &Vec<Setting> {
+        &self.settings
+    }
+
+    /// Get the hooks
+    pub fn hooks(&self) -> &Vec<Hook> {
+        &self.hooks
+    }
+
+    /// Get the hooks that run when the profile is unset
+    pub fn unset_hooks(&self) -> &Vec<Hook> {
+        &self.unset_hooks
+    }
+}
+
+/// Resolves the path of a profile relative to the profiles directory
+fn resolve(config: &Config, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_owned()
     } else {
-        // FIXME: better error handling
-        config_dir()
-            .expect("config dir borked")
-            .join("rconfigure/profiles")
-            .join(path)
-    };
-
-    // FIXME: handle errors for file not found
-    let s = fs::read_to_string(&path).unwrap();
-    let profile: ProfileDeserialized = match toml::from_str(s.as_str()) {
-        Ok(value) => value,
-        Err(e) => {
-            println!("error when parsing setting {:?}", path);
-            println!("{}", e);
-            std::process::exit(1);
+        config.profiles_dir().join(path)
+    }
+}
+
+/// Parses a profile and all of its settings, including the ones of the profiles it extends
+pub fn parse<P: AsRef<Path>>(config: &Config, path: P) -> Result<Profile, CrateError> {
+    parse_extending(config, path.as_ref(), &mut Vec::new())
+}
+
+/// Parses a profile as part of a chain of profiles extending each other
+fn parse_extending(
+    config: &Config,
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Profile, CrateError> {
+    let path = resolve(config, path);
+
+    if chain.contains(&path) {
+        return Err(ProfileError::InheritanceCycle(path, chain.to_owned()).into());
+    }
+
+    let s = fs::read_to_string(&path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => ProfileError::MissingProfile(path.to_owned()),
+        _ => ProfileError::ErrorReadingProfile(path.to_owned(), err),
+    })?;
+
+    let profile: ProfileDeserialized = toml::from_str(s.as_str())
+        .map_err(|err| ProfileError::ErrorParsingProfile(path.to_owned(), err))?;
+
+    let table = profile.profile_table;
+    let mut extends = Vec::new();
+    let mut settings_buf: Vec<Setting> = Vec::new();
+    let mut hook_sources: Vec<HookSource> = Vec::new();
+    let mut strict = false;
+
+    // settings and hooks of the extended profiles come first so this profile can override them
+    chain.push(path.to_owned());
+
+    for base in table.iter().flat_map(|t| t.extends.iter().flatten()) {
+        let base = parse_extending(config, Path::new(base), chain)?;
+
+        for setting in base.settings {
+            if !settings_buf.iter().any(|s| s.path() == setting.path()) {
+                settings_buf.push(setting);
+            }
+        }
+
+        // a profile extended through several others only runs its hooks once
+        for source in base.hook_sources {
+            if !hook_sources.iter().any(|s| s.profile == source.profile) {
+                hook_sources.push(source);
+            }
         }
-    };
 
-    let mut settings_buf = Vec::new();
+        strict |= base.strict;
+        extends.push(base.path);
+    }
+
+    chain.pop();
+
+    let inherited = settings_buf.iter().map(Setting::path).collect();
 
     // parse all of the settings and add them to the vector
     if let Some(ProfileTable {
         settings: Some(settings),
         ..
-    }) = &profile.profile_table
+    }) = &table
     {
-        for setting in settings {
-            let path = PathBuf::from(setting);
+        for entry in settings {
+            let mut setting = setting::parse(config, PathBuf::from(entry.path()))?;
+
+            if let SettingEntry::Inline { params, .. } = entry {
+                setting = setting.with_params(params.to_owned());
+            }
 
-            settings_buf.push(setting::parse(path));
+            // listing an inherited setting again moves it after the others
+            settings_buf.retain(|s| s.path() != setting.path());
+            settings_buf.push(setting);
         }
     }
 
-    Profile {
-        name: profile
-            .profile_table
+    if let Some(ProfileTable {
+        remove_settings: Some(remove_settings),
+        ..
+    }) = &table
+    {
+        for setting in remove_settings {
+            let removed = setting::parse(config, PathBuf::from(setting))?;
+            settings_buf.retain(|s| s.path() != removed.path());
+        }
+    }
+
+    hook_sources.push(HookSource {
+        profile: path.to_owned(),
+        hooks: table.as_ref().map(|t| t.hooks.clone()).unwrap_or_default(),
+        unset_hooks: table
+            .as_ref()
+            .map(|t| t.unset_hooks.clone())
+            .unwrap_or_default(),
+    });
+
+    Ok(Profile {
+        config: config.to_owned(),
+        name: table
             .as_ref()
             .and_then(|t| t.name.clone())
-            .unwrap_or_else(|| path.file_name().unwrap().to_str().unwrap().to_string()),
+            .unwrap_or_else(|| file_name(&path)),
+        strict: table.as_ref().and_then(|t| t.strict).unwrap_or(strict),
+        extends,
         settings: settings_buf,
-        hooks: profile
-            .profile_table
-            .and_then(|t| Some(t.hooks))
-            .unwrap_or_default(),
+        inherited,
+        hooks: hook_sources.iter().flat_map(|s| s.hooks.clone()).collect(),
+        unset_hooks: hook_sources
+            .iter()
+            .flat_map(|s| s.unset_hooks.clone())
+            .collect(),
+        hook_sources,
         path,
-    }
+    })
+}
+
+/// Lists the path of every profile in the profiles directory
+pub fn list(config: &Config) -> Result<Vec<PathBuf>, CrateError> {
+    walk::list(&config.profiles_dir())
+}
+
+/// Gets the file name of a path as a string to use as a fallback display name
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
 }
@@ -0,0 +1,62 @@
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::setting::{Rendered, Setting};
+use rhai::Engine;
+use std::collections::HashMap;
+
+impl Profile {
+    /// Get the settings in the order their values are layered, so each one overrides the values
+    /// of the ones before it
+    pub fn layered<'a>(&'a self, new_setting: Option<&'a Setting>) -> Vec<&'a Setting> {
+        let mut settings: Vec<&Setting> = self.settings.iter().chain(new_setting).collect();
+
+        // the sort is stable so later settings still win among equal priorities
+        settings.sort_by_key(|setting| setting.priority());
+        settings
+    }
+
+    /// Renders every template targeted by the settings of the profile, layering the values of
+    /// every setting targeting the same template, along with every setting that contributed to
+    /// each output
+    pub fn render(&self, engine: &Engine) -> Result<Vec<(Rendered, Vec<&Setting>)>, CrateError> {
+        let settings = self.layered(None);
+        let mut templates = Vec::new();
+
+        for setting in &settings {
+            for target in setting.templates() {
+                if !templates.contains(&target) {
+                    templates.push(target);
+                }
+            }
+        }
+
+        let mut outputs = Vec::new();
+
+        for template in templates {
+            let mut map = HashMap::new();
+            let mut contributors = Vec::new();
+
+            for setting in &settings {
+                if setting.templates().contains(&template) {
+                    map.extend(setting.values(setting.compose_map(&template), engine)?);
+                    contributors.push(*setting);
+                }
+            }
+
+            // the last setting to add values owns the output and names its managed block
+            if let Some(owner) = contributors.last() {
+                if let Some(rendered) = owner.render_template(&template, map)? {
+                    outputs.push((rendered, contributors));
+                }
+            }
+        }
+
+        for setting in settings {
+            for rendered in setting.render_merges(engine)? {
+                outputs.push((rendered, vec![setting]));
+            }
+        }
+
+        Ok(outputs)
+    }
+}
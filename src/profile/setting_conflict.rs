@@ -1,39 +1,83 @@
+use crate::errors::ProfileError;
 use crate::{profile::Profile, setting::Setting};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 impl Profile {
-    /// Checks for settings with conflicting targets and returns a tuple of the first two conflicting
-    /// setting files and their conflicting target
-    pub fn setting_conflict<'a>(
+    /// Finds every key set by more than one setting, returning the overridden setting, the setting
+    /// overriding it, the target and the key
+    pub fn conflicts<'a>(
         &'a self,
         new_setting: Option<&'a Setting>,
-    ) -> Option<(Setting, &'a Setting, PathBuf)> {
-        let mut targets = HashMap::new();
-
-        // check for conflicts in profile settings
-        for setting in self.settings.iter() {
-            for target in setting.targets() {
-                if !targets.contains_key(&target) {
-                    targets.insert(target, setting);
-                } else {
-                    return Some(((*targets.get(&target).unwrap()).to_owned(), setting, target));
+    ) -> Vec<(&'a Setting, &'a Setting, PathBuf, String)> {
+        let mut conflicts = Vec::new();
+        let mut keys: HashMap<(PathBuf, String), &Setting> = HashMap::new();
+        let mut merge_keys: Vec<(PathBuf, Vec<String>, &Setting)> = Vec::new();
+
+        for setting in self.layered(new_setting) {
+            // template values conflict per key after composing the path walk of each setting
+            for target in setting.templates() {
+                for key in setting.compose_map(&target).into_keys() {
+                    let entry = (target.to_owned(), key);
+
+                    if let Some(existing) = keys.insert(entry.clone(), setting) {
+                        conflicts.push((existing, setting, entry.0, entry.1));
+                    }
                 }
             }
-        }
 
-        // check for conflicts between the settings of the profile and a new setting
-        if let Some(setting) = new_setting {
-            for target in setting.targets() {
-                if !targets.contains_key(&target) {
-                    targets.insert(target, &setting);
-                } else {
-                    // existing setting, enabled setting, conflicting target
-                    return Some(((*targets.get(&target).unwrap()).to_owned(), setting, target));
+            // structured files conflict where the key paths of two settings overlap
+            for (path, key) in setting.merge_keys() {
+                for (other_path, other_key, existing) in &merge_keys {
+                    if *other_path == path
+                        && (other_key.starts_with(&key) || key.starts_with(other_key))
+                    {
+                        // report the more specific of the two keys
+                        let key = if other_key.len() > key.len() {
+                            other_key
+                        } else {
+                            &key
+                        };
+
+                        conflicts.push((*existing, setting, path.to_owned(), key.join(".")));
+                    }
                 }
+
+                merge_keys.push((path, key, setting));
             }
         }
 
-        None
+        conflicts
+    }
+
+    /// Checks for settings setting the same key and returns a tuple of the first two conflicting
+    /// setting files, their conflicting target and key
+    pub fn setting_conflict<'a>(
+        &'a self,
+        new_setting: Option<&'a Setting>,
+    ) -> Option<(Setting, &'a Setting, PathBuf, String)> {
+        self.conflicts(new_setting)
+            .into_iter()
+            .next()
+            .map(|(existing, setting, target, key)| (existing.to_owned(), setting, target, key))
+    }
+
+    /// Returns an error describing the first setting conflict of a strict profile if there is one,
+    /// other profiles let settings override each other
+    pub fn check_conflicts(&self) -> Result<(), ProfileError> {
+        if !self.strict {
+            return Ok(());
+        }
+
+        match self.setting_conflict(None) {
+            Some((setting1, setting2, target, key)) => Err(ProfileError::SettingConflict {
+                setting1: setting1.name(),
+                setting2: setting2.name(),
+                target,
+                key,
+            }),
+
+            None => Ok(()),
+        }
     }
 }
@@ -0,0 +1,28 @@
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::state::{OutputStatus, State};
+use rhai::Engine;
+use std::path::PathBuf;
+
+impl Profile {
+    /// Gets every output managed by the profile and how it compares to what was last applied
+    pub fn status(&self, engine: &Engine) -> Result<Vec<(PathBuf, OutputStatus)>, CrateError> {
+        let state = State::load(&self.config)?;
+        let mut paths: Vec<PathBuf> = state.outputs().keys().cloned().collect();
+
+        // outputs of settings enabled since the last apply have not been recorded yet
+        for (rendered, ..) in self.render(engine)? {
+            if !paths.contains(&rendered.path) {
+                paths.push(rendered.path);
+            }
+        }
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let status = state.status(&path)?;
+                Ok((path, status))
+            })
+            .collect()
+    }
+}
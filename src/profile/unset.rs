@@ -0,0 +1,40 @@
+use crate::config::Config;
+use crate::errors::CrateError;
+use crate::profile::Profile;
+use crate::state::State;
+use crate::{block, originals};
+
+/// Restores every file the last applied profile overwrote and removes its managed blocks, which
+/// only needs what was recorded when it was applied
+pub(crate) fn restore(config: &Config) -> Result<(), CrateError> {
+    for (output, record) in State::load(config)?.outputs() {
+        if record.block() {
+            block::remove_from_file(output, None, record.comment())?;
+        }
+    }
+
+    originals::restore_all(config)?;
+
+    // nothing is managed anymore once the original files are back
+    State::default().save(config)
+}
+
+impl Profile {
+    /// Restores every file the profile overwrote, removes its managed blocks and runs the unset
+    /// hooks
+    pub fn unset(&self) -> Result<(), CrateError> {
+        restore(&self.config)?;
+
+        for setting in &self.settings {
+            for hook in setting.unset_hooks() {
+                hook.run();
+            }
+        }
+
+        for hook in &self.unset_hooks {
+            hook.run();
+        }
+
+        Ok(())
+    }
+}
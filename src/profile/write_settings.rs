@@ -0,0 +1,200 @@
+use crate::config::Config;
+use crate::errors::{CrateError, ProfileError};
+use crate::profile::Profile;
+use crate::setting::{self, Setting};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, Item, RawString, Table, TableLike, Value};
+
+/// Get the setting path an entry of a settings array lists, as it is written
+fn entry_path(value: &Value) -> Option<&str> {
+    value
+        .as_str()
+        .or_else(|| value.as_inline_table()?.get("setting")?.as_str())
+}
+
+/// Writes the path of a setting relative to the settings directory when it is inside it, so the
+/// profile keeps working when the config directory moves
+fn short_name(config: &Config, path: &Path) -> String {
+    path.strip_prefix(config.settings_dir())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Splits spacing at its last line break, before which is the comment ending the line above
+fn split_comment(raw: &str) -> (&str, &str) {
+    match raw.rfind('\n') {
+        Some(i) => raw.split_at(i),
+        None => ("", raw),
+    }
+}
+
+/// Replaces the entries of an array with one for each setting path, keeping entries that were
+/// already listed as they were written along with the comments after them
+fn replace_entries(config: &Config, array: &mut Array, paths: &[PathBuf]) {
+    let raw = |raw: Option<&RawString>| {
+        raw.and_then(RawString::as_str)
+            .unwrap_or_default()
+            .to_owned()
+    };
+    let prefixes: Vec<String> = array.iter().map(|v| raw(v.decor().prefix())).collect();
+    let trailing = raw(Some(array.trailing()));
+
+    // the spacing before an entry or the end of the array starts with the comment ending the
+    // line of the entry before, and the first entry is usually spaced differently from the rest
+    let (leading, first) = split_comment(prefixes.first().map(String::as_str).unwrap_or_default());
+    let rest = prefixes
+        .get(1)
+        .map(|p| split_comment(p).1)
+        .or_else(|| Some(first).filter(|p| p.contains('\n')))
+        .unwrap_or(" ");
+    let (_, closing) = split_comment(&trailing);
+
+    let existing: Vec<(usize, PathBuf, Value)> = array
+        .iter()
+        .enumerate()
+        .filter_map(|(i, value)| {
+            let path = setting::resolve(config, Path::new(entry_path(value)?));
+            Some((i, path, value.to_owned()))
+        })
+        .collect();
+
+    // the comment after an entry
+    let comment = |index: Option<usize>| match index {
+        Some(i) => split_comment(prefixes.get(i + 1).unwrap_or(&trailing)).0,
+        None => "",
+    };
+
+    let mut entries = Vec::new();
+    let mut previous = None;
+
+    for (i, path) in paths.iter().enumerate() {
+        let entry = existing.iter().find(|(_, p, _)| p == path);
+        let index = entry.map(|(index, ..)| *index);
+
+        let (mut value, spacing) = match entry {
+            // entries only keep their own spacing if they stay first or stay after the first
+            Some((index, _, value)) if (*index == 0) == (i == 0) => {
+                (value.to_owned(), split_comment(&prefixes[*index]).1)
+            }
+
+            Some((_, _, value)) => (value.to_owned(), if i == 0 { first } else { rest }),
+            None => (
+                Value::from(short_name(config, path)),
+                if i == 0 { first } else { rest },
+            ),
+        };
+
+        let comment = if i == 0 { leading } else { comment(previous) };
+        value
+            .decor_mut()
+            .set_prefix(format!("{}{}", comment, spacing));
+
+        entries.push(value);
+        previous = index;
+    }
+
+    let last = if entries.is_empty() {
+        ""
+    } else {
+        comment(previous)
+    };
+    array.set_trailing(format!("{}{}", last, closing));
+
+    array.clear();
+    for value in entries {
+        array.push_formatted(value);
+    }
+}
+
+/// Get an array of a profile table, adding an empty one when the profile doesn't have it yet
+fn array_mut<'a>(
+    path: &Path,
+    table: &'a mut dyn TableLike,
+    key: &str,
+) -> Result<&'a mut Array, ProfileError> {
+    table
+        .entry(key)
+        .or_insert_with(|| Item::Value(Array::new().into()))
+        .as_array_mut()
+        .ok_or_else(|| ProfileError::UnexpectedType {
+            path: path.to_owned(),
+            key: key.to_owned(),
+            expected: "an array",
+        })
+}
+
+impl Profile {
+    /// Writes the enabled settings back into the profile file, leaving everything but its
+    /// `settings` and `remove_settings` arrays as it was written
+    pub(crate) fn write_settings(&self) -> Result<(), CrateError> {
+        let contents = self.settings_document()?;
+
+        fs::write(&self.path, contents)
+            .map_err(|err| ProfileError::ErrorWritingProfile(self.path.to_owned(), err))?;
+
+        Ok(())
+    }
+
+    /// Get the contents of the profile file with the enabled settings written into it
+    pub(crate) fn settings_document(&self) -> Result<String, CrateError> {
+        let s = fs::read_to_string(&self.path)
+            .map_err(|err| ProfileError::ErrorReadingProfile(self.path.to_owned(), err))?;
+
+        let mut document: DocumentMut = s
+            .parse()
+            .map_err(|err| ProfileError::ErrorEditingProfile(self.path.to_owned(), err))?;
+
+        // profiles that only extend others or list nothing yet don't need a `[profile]` table
+        let table = document
+            .entry("profile")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| ProfileError::UnexpectedType {
+                path: self.path.to_owned(),
+                key: String::from("profile"),
+                expected: "a table",
+            })?;
+
+        let listed: Vec<PathBuf> = match table.get("settings") {
+            Some(settings) => settings
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(entry_path)
+                .map(|path| setting::resolve(&self.config, Path::new(path)))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // inherited settings are only listed when the profile lists them itself
+        let own: Vec<PathBuf> = self
+            .settings
+            .iter()
+            .map(Setting::path)
+            .filter(|path| !self.inherited.contains(path) || listed.contains(path))
+            .collect();
+
+        if !own.is_empty() || table.contains_key("settings") {
+            let settings = array_mut(&self.path, table, "settings")?;
+            replace_entries(&self.config, settings, &own);
+        }
+
+        let removed: Vec<PathBuf> = self
+            .inherited
+            .iter()
+            .filter(|path| !self.settings.iter().any(|s| s.path() == **path))
+            .cloned()
+            .collect();
+
+        if removed.is_empty() {
+            table.remove("remove_settings");
+        } else {
+            let remove_settings = array_mut(&self.path, table, "remove_settings")?;
+            replace_entries(&self.config, remove_settings, &removed);
+        }
+
+        Ok(document.to_string())
+    }
+}
@@ -1,12 +1,7 @@
-mod flatten;
-
+use crate::errors::{CrateError, SettingError};
 use rhai::{serde::from_dynamic, Dynamic, Engine, Scope};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, iter::FromIterator, path::PathBuf, str::FromStr};
-
-pub trait Flatten {
-    fn flatten(self, name: String) -> HashMap<String, String>;
-}
+use std::{collections::HashMap, iter::FromIterator, path::PathBuf};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
@@ -20,20 +15,20 @@ pub enum ScriptValue {
 }
 
 impl ScriptValue {
-    fn to_dynamic(self) -> Dynamic {
+    fn into_dynamic(self) -> Dynamic {
         match self {
             ScriptValue::Boolean(value) => Dynamic::from_bool(value),
             ScriptValue::Integer(value) => Dynamic::from_int(value),
             ScriptValue::Float(value) => Dynamic::from_float(value),
-            ScriptValue::String(value) => Dynamic::from_str(value.as_str()).unwrap(),
+            ScriptValue::String(value) => Dynamic::from(value),
             ScriptValue::Array(value) => {
-                Dynamic::from_iter(value.into_iter().map(|value| value.to_dynamic()))
+                Dynamic::from_iter(value.into_iter().map(|value| value.into_dynamic()))
             }
             ScriptValue::Map(value) => {
                 let mut map = rhai::Map::new();
 
                 for (k, v) in value {
-                    map.insert(k.into(), v.to_dynamic());
+                    map.insert(k.into(), v.into_dynamic());
                 }
 
                 Dynamic::from(map)
@@ -46,30 +41,24 @@ pub fn eval_rhai(
     path: PathBuf,
     value: ScriptValue,
     engine: &Engine,
-) -> HashMap<String, ScriptValue> {
+) -> Result<HashMap<String, ScriptValue>, CrateError> {
     let mut scope = Scope::new();
 
-    scope.push_constant("value", value.to_dynamic());
-
-    match engine.eval_file_with_scope::<rhai::Map>(&mut scope, path.clone()) {
-        Ok(btree) => {
-            let mut map = HashMap::new();
+    scope.push_constant("value", value.into_dynamic());
 
-            for (k, v) in btree {
-                // FIXME: change this to not use from_dynamic
-                let v: ScriptValue = from_dynamic(&v).unwrap();
+    let btree = engine
+        .eval_file_with_scope::<rhai::Map>(&mut scope, path.clone())
+        .map_err(|err| SettingError::ScriptFailure(path.clone(), err))?;
 
-                map.insert(k.to_string(), v);
-            }
+    let mut map = HashMap::new();
 
-            return map;
-        }
+    for (k, v) in btree {
+        // FIXME: change this to not use from_dynamic
+        let v: ScriptValue =
+            from_dynamic(&v).map_err(|err| SettingError::ScriptFailure(path.clone(), err))?;
 
-        Err(e) => {
-            println!("Error in rhai script {:?}", path);
-            println!("{}", e);
-        }
-    };
+        map.insert(k.to_string(), v);
+    }
 
-    HashMap::new()
+    Ok(map)
 }
@@ -1,21 +1,47 @@
 use crate::setting::{Setting, TargetValue};
-use dirs::config_dir;
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Where in a setting a value composed for a template comes from
+#[derive(Clone, PartialEq, Debug)]
+pub enum Origin {
+    /// The values the profile layers on top of the setting
+    Profile,
+    /// The target table for this path, either the template itself or one of its parent directories
+    Target(PathBuf),
+    /// The `[global]` table
+    Global,
+}
 
 impl Setting {
     /// Composes a map from all of the setting targets for a given path
     pub fn compose_map<P: AsRef<Path>>(&self, path: P) -> HashMap<String, TargetValue> {
+        self.compose_origins(path)
+            .into_iter()
+            .map(|(k, (v, _))| (k, v))
+            .collect()
+    }
+
+    /// Composes a map from all of the setting targets for a given path, along with where each
+    /// value was found
+    pub fn compose_origins<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> HashMap<String, (TargetValue, Origin)> {
         let mut path = if path.as_ref().is_absolute() {
             path.as_ref().to_owned()
         } else {
-            // FIXME: better error handling
-            config_dir()
-                .expect("config dir borked")
-                .join("rconfigure/templates")
-                .join(path)
+            self.config.templates_dir().join(path)
         };
 
-        let mut map: HashMap<String, TargetValue> = HashMap::new();
+        // values from the profile take precedence over everything in the setting file
+        let mut map: HashMap<String, (TargetValue, Origin)> = self
+            .params
+            .iter()
+            .map(|(k, v)| (k.to_owned(), (v.to_owned(), Origin::Profile)))
+            .collect();
 
         loop {
             // check each of the target paths
@@ -24,7 +50,8 @@ impl Setting {
                 if path == *target_path {
                     for (k, v) in table {
                         if !map.contains_key(k) {
-                            map.insert(k.to_owned(), v.to_owned());
+                            let origin = Origin::Target(target_path.to_owned());
+                            map.insert(k.to_owned(), (v.to_owned(), origin));
                         }
                     }
                 }
@@ -37,7 +64,7 @@ impl Setting {
                 if let Some(table) = &self.global_target {
                     for (k, v) in table {
                         if !map.contains_key(k) {
-                            map.insert(k.to_owned(), v.to_owned());
+                            map.insert(k.to_owned(), (v.to_owned(), Origin::Global));
                         }
                     }
                 }
@@ -1,14 +1,21 @@
-mod apply;
 mod compose_map;
+mod render;
 
+pub use compose_map::Origin;
+pub use render::Rendered;
+
+use crate::config::Config;
+use crate::errors::{CrateError, SettingError};
 use crate::hook::Hook;
+use crate::merge::Format;
 use crate::script::ScriptValue;
-use dirs::config_dir;
-use serde::Deserialize;
+use crate::{profile, template, walk};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::{collections::HashMap, fs};
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum TargetValue {
     Boolean(bool),
@@ -16,6 +23,8 @@ pub enum TargetValue {
     Float(f64),
     String(String),
     Script { script: String, value: ScriptValue },
+    Array(Vec<ScriptValue>),
+    Table(HashMap<String, TargetValue>),
 }
 
 #[derive(Deserialize)]
@@ -24,29 +33,90 @@ struct SettingDeserialized {
     setting_table: Option<SettingTable>,
     #[serde(rename = "global")]
     global_target: Option<HashMap<String, TargetValue>>,
+    #[serde(rename = "merge", default)]
+    merge_targets: HashMap<String, MergeTable>,
     #[serde(flatten)]
     targets: HashMap<PathBuf, HashMap<String, TargetValue>>,
 }
 
+/// A `[merge."<path>"]` table with the values to merge into a structured file
+#[derive(Deserialize)]
+struct MergeTable {
+    /// The format of the file, for files whose extension doesn't name it
+    format: Option<Format>,
+    #[serde(flatten)]
+    values: HashMap<String, TargetValue>,
+}
+
+/// The fields a `[setting]` table can have
+pub(crate) const FIELDS: &[&str] = &[
+    "name",
+    "extends",
+    "hooks",
+    "unset_hooks",
+    "disable_hooks",
+    "priority",
+];
+
 #[derive(Deserialize)]
 struct SettingTable {
     name: Option<String>,
+    extends: Option<PathBuf>,
     #[serde(default)]
     hooks: Vec<Hook>,
+    #[serde(default)]
+    unset_hooks: Vec<Hook>,
+    #[serde(default)]
+    disable_hooks: Vec<Hook>,
+    priority: Option<i64>,
 }
 
 // FIXME: Setting can derive `Deserialize`, can we refactor SettingDeserialized out?
 #[derive(Clone, Debug)]
 pub struct Setting {
+    config: Config,
     name: String,
+    priority: i64,
     hooks: Vec<Hook>,
+    unset_hooks: Vec<Hook>,
+    disable_hooks: Vec<Hook>,
     path: PathBuf,
+    extends: Option<PathBuf>,
     global_target: Option<HashMap<String, TargetValue>>,
     targets: Vec<(PathBuf, HashMap<String, TargetValue>)>,
+    merges: Vec<(PathBuf, Format, HashMap<String, TargetValue>)>,
+    params: HashMap<String, TargetValue>,
+}
+
+/// Collects the key path of every value in a table, where a script claims the whole table it is
+/// in since the keys it produces aren't known until it runs
+fn key_paths(
+    table: &HashMap<String, TargetValue>,
+    prefix: &[String],
+    paths: &mut Vec<Vec<String>>,
+) {
+    for (k, v) in table {
+        let mut path = prefix.to_vec();
+
+        match v {
+            TargetValue::Table(table) => {
+                path.push(k.to_owned());
+                key_paths(table, &path, paths);
+            }
+
+            TargetValue::Script { .. } => paths.push(path),
+
+            _ => {
+                path.push(k.to_owned());
+                paths.push(path);
+            }
+        }
+    }
 }
 
 impl Setting {
-    /// Get the paths for all the targeted templates
+    /// Get the paths for all the targeted templates, including directories whose values only
+    /// reach the templates inside them
     pub fn targets(&self) -> Vec<PathBuf> {
         self.targets
             .iter()
@@ -54,75 +124,273 @@ impl Setting {
             .collect()
     }
 
+    /// Get the paths of the targeted templates, leaving out directories
+    pub fn templates(&self) -> Vec<PathBuf> {
+        self.targets()
+            .into_iter()
+            .filter(|path| !path.is_dir())
+            .collect()
+    }
+
+    /// Get the values of every target table as written in the setting, including the ones of
+    /// directories
+    pub fn target_tables(&self) -> &[(PathBuf, HashMap<String, TargetValue>)] {
+        &self.targets
+    }
+
+    /// Get the values of the `[global]` table
+    pub fn global_target(&self) -> Option<&HashMap<String, TargetValue>> {
+        self.global_target.as_ref()
+    }
+
+    /// Get the paths of the structured files the setting merges values into
+    pub fn merges(&self) -> Vec<PathBuf> {
+        self.merges
+            .iter()
+            .map(|(path, ..)| path.to_owned())
+            .collect()
+    }
+
+    /// Get the format of a structured file if the setting merges into it
+    pub(crate) fn merge_format(&self, path: &Path) -> Option<Format> {
+        self.merges
+            .iter()
+            .find(|(merge, ..)| merge == path)
+            .map(|(_, format, _)| *format)
+    }
+
+    /// Get every structured file the setting merges into along with the key paths it sets
+    pub fn merge_keys(&self) -> Vec<(PathBuf, Vec<String>)> {
+        let mut keys = Vec::new();
+
+        for (path, _, table) in &self.merges {
+            let mut paths = Vec::new();
+            key_paths(table, &[], &mut paths);
+
+            keys.extend(paths.into_iter().map(|key| (path.to_owned(), key)));
+        }
+
+        keys
+    }
+
     /// Get the path of the setting file
     pub fn path(&self) -> PathBuf {
         self.path.to_owned()
     }
 
+    /// Get the path of the setting file relative to the settings directory, which identifies its
+    /// managed blocks
+    pub fn id(&self) -> String {
+        self.path
+            .strip_prefix(self.config.settings_dir())
+            .unwrap_or(&self.path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
     /// Get the hooks
     pub fn hooks(&self) -> &Vec<Hook> {
         &self.hooks
     }
 
+    /// Get the hooks that run when the setting is unset
+    pub fn unset_hooks(&self) -> &Vec<Hook> {
+        &self.unset_hooks
+    }
+
+    /// Get the hooks that run when the setting is disabled
+    pub fn disable_hooks(&self) -> &Vec<Hook> {
+        &self.disable_hooks
+    }
+
     /// Get the name of the setting
     pub fn name(&self) -> String {
         self.name.to_owned()
     }
+
+    /// Get the priority of the setting, settings with a higher one override the values of others
+    pub fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    /// Get the path of the setting this one extends
+    pub fn extends(&self) -> Option<&Path> {
+        self.extends.as_deref()
+    }
+
+    /// Get the values a profile layers on top of every target of the setting
+    pub fn params(&self) -> &HashMap<String, TargetValue> {
+        &self.params
+    }
+
+    /// Layers values from a profile on top of every target of the setting
+    pub(crate) fn with_params(mut self, params: HashMap<String, TargetValue>) -> Self {
+        self.params = params;
+        self
+    }
+}
+
+/// Layers tables of values over the tables they inherit, key by key
+fn overlay(
+    base: &mut Vec<(PathBuf, HashMap<String, TargetValue>)>,
+    tables: Vec<(PathBuf, HashMap<String, TargetValue>)>,
+) {
+    for (path, table) in tables {
+        match base.iter_mut().find(|(base_path, _)| *base_path == path) {
+            Some((_, base_table)) => base_table.extend(table),
+            None => base.push((path, table)),
+        }
+    }
 }
 
-/// Parses a setting into its struct representation
-pub fn parse<P: AsRef<Path>>(path: P) -> Setting {
-    let path = if path.as_ref().is_absolute() {
-        path.as_ref().to_owned()
+/// Lists the path of every setting in the settings directory
+pub fn list(config: &Config) -> Result<Vec<PathBuf>, CrateError> {
+    walk::list(&config.settings_dir())
+}
+
+/// Resolves the path of a setting relative to the settings directory
+pub(crate) fn resolve(config: &Config, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_owned()
     } else {
-        // FIXME: better error handling
-        config_dir()
-            .expect("config dir borked")
-            .join("rconfigure/settings")
-            .join(path)
-    };
-
-    // FIXME: handle errors for file not found
-    let s = fs::read_to_string(&path).unwrap();
-    let setting: SettingDeserialized = match toml::from_str(s.as_str()) {
-        Ok(value) => value,
-        Err(e) => {
-            println!("error when parsing setting {:?}", path);
-            println!("{}", e);
-            std::process::exit(1);
+        config.settings_dir().join(path)
+    }
+}
+
+/// Parses a setting into its struct representation, including what it inherits from the setting
+/// it extends
+pub fn parse<P: AsRef<Path>>(config: &Config, path: P) -> Result<Setting, CrateError> {
+    parse_extending(config, path.as_ref(), &mut Vec::new())
+}
+
+/// Parses a setting as part of a chain of settings extending each other
+fn parse_extending(
+    config: &Config,
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Setting, CrateError> {
+    let path = resolve(config, path);
+
+    if chain.contains(&path) {
+        return Err(SettingError::InheritanceCycle(path, chain.to_owned()).into());
+    }
+
+    let s = fs::read_to_string(&path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => SettingError::MissingSetting(path.to_owned()),
+        _ => SettingError::ErrorReadingSetting(path.to_owned(), err),
+    })?;
+
+    let setting: SettingDeserialized = toml::from_str(s.as_str())
+        .map_err(|err| SettingError::ErrorParsingSetting(path.to_owned(), err))?;
+
+    let mut targets = Vec::new();
+    let mut merges = Vec::new();
+
+    for (target, table) in setting.targets {
+        if target.is_absolute() {
+            targets.push((target, table));
+        } else {
+            targets.push((config.templates_dir().join(target), table));
         }
-    };
-
-    Setting {
-        name: match setting.setting_table {
-            Some(SettingTable {
-                name: Some(ref name),
-                ..
-            }) => name.clone(),
-            _ => path.file_name().unwrap().to_str().unwrap().to_string(),
-        },
-        hooks: match setting.setting_table {
-            Some(SettingTable { hooks, .. }) => hooks,
-            _ => Vec::new(),
-        },
-        path,
-        global_target: setting.global_target,
-        targets: setting
-            .targets
-            .into_iter()
-            .map(|(path, table)| {
-                if path.is_absolute() {
-                    (path, table)
-                } else {
-                    // FIXME: better error handling
-                    let path = config_dir()
-                        .expect("config dir borked")
-                        .join("rconfigure/templates")
-                        .join(path);
-
-                    (path, table)
+    }
+
+    // structured files are merged into where they are, so their paths can't be relative
+    for (target, table) in setting.merge_targets {
+        let target = template::expand_vars(&path, &target)?;
+
+        if !target.is_absolute() {
+            return Err(SettingError::RelativeMergeTarget(path, target).into());
+        }
+
+        let format = match table.format.or_else(|| Format::from_path(&target)) {
+            Some(format) => format,
+            None => return Err(SettingError::UnknownFormat(path, target).into()),
+        };
+
+        merges.push((target, format, table.values));
+    }
+
+    let table = setting.setting_table;
+    let mut global_target = setting.global_target;
+    let mut hooks = Vec::new();
+    let mut unset_hooks = Vec::new();
+    let mut disable_hooks = Vec::new();
+    let mut priority = None;
+    let mut extends = None;
+
+    // the extended setting provides every value this one does not set itself
+    if let Some(base) = table.as_ref().and_then(|t| t.extends.as_ref()) {
+        chain.push(path.to_owned());
+        let base = parse_extending(config, base, chain)?;
+        chain.pop();
+
+        let mut base_targets = base.targets;
+        overlay(&mut base_targets, targets);
+        targets = base_targets;
+
+        let mut base_merges = base.merges;
+        for (path, format, table) in merges {
+            match base_merges
+                .iter_mut()
+                .find(|(base_path, ..)| *base_path == path)
+            {
+                Some((_, base_format, base_table)) => {
+                    *base_format = format;
+                    base_table.extend(table);
                 }
-            })
-            .collect::<Vec<_>>(),
+
+                None => base_merges.push((path, format, table)),
+            }
+        }
+        merges = base_merges;
+
+        if let Some(base_global) = base.global_target {
+            let mut base_global = base_global;
+            base_global.extend(global_target.unwrap_or_default());
+            global_target = Some(base_global);
+        }
+
+        hooks = base.hooks;
+        unset_hooks = base.unset_hooks;
+        disable_hooks = base.disable_hooks;
+        priority = Some(base.priority);
+        extends = Some(base.path);
     }
+
+    Ok(Setting {
+        config: config.to_owned(),
+        name: table
+            .as_ref()
+            .and_then(|t| t.name.clone())
+            .unwrap_or_else(|| profile::file_name(&path)),
+        priority: table
+            .as_ref()
+            .and_then(|t| t.priority)
+            .or(priority)
+            .unwrap_or_default(),
+        hooks: [
+            hooks,
+            table.as_ref().map(|t| t.hooks.clone()).unwrap_or_default(),
+        ]
+        .concat(),
+        unset_hooks: [
+            unset_hooks,
+            table
+                .as_ref()
+                .map(|t| t.unset_hooks.clone())
+                .unwrap_or_default(),
+        ]
+        .concat(),
+        disable_hooks: [
+            disable_hooks,
+            table.map(|t| t.disable_hooks).unwrap_or_default(),
+        ]
+        .concat(),
+        path,
+        extends,
+        global_target,
+        targets,
+        merges,
+        params: HashMap::new(),
+    })
 }
@@ -0,0 +1,171 @@
+use crate::block;
+use crate::errors::CrateError;
+use crate::merge;
+use crate::script::{self, ScriptValue};
+use crate::setting::{Setting, TargetValue};
+use crate::template::{self, Header};
+use rhai::Engine;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A target of a setting that has been templated
+#[derive(Clone, Debug)]
+pub struct Rendered {
+    /// The template that was rendered, or the setting itself for a structured file
+    pub template: PathBuf,
+    /// The path from the template header the contents get written to
+    pub path: PathBuf,
+    /// The metadata from the template header
+    pub header: Header,
+    /// The id of the setting, naming its managed block
+    pub setting: String,
+    /// The rendered template, or the values as JSON for a structured file
+    pub contents: String,
+}
+
+impl Rendered {
+    /// Get the contents in the encoding the template asks for
+    pub fn bytes(&self) -> Vec<u8> {
+        self.header.encode(&self.contents)
+    }
+
+    /// Get what the output file contains once written over its current contents, which only
+    /// matter for managed blocks and structured files
+    pub fn output(&self, current: Option<&[u8]>) -> Result<Vec<u8>, CrateError> {
+        let current = current.map(|c| self.header.decode(c)).unwrap_or_default();
+
+        if let Some(format) = self.header.merge_format() {
+            let values = merge::parse(merge::Format::Json, &self.path, &self.contents)?;
+
+            return Ok(self
+                .header
+                .encode(&merge::merge(format, &self.path, &current, values)?));
+        }
+
+        if !self.header.block() {
+            return Ok(self.bytes());
+        }
+
+        Ok(self.header.encode(&block::insert(
+            &current,
+            &self.setting,
+            self.header.comment(),
+            &self.contents,
+            self.header.newline_str(),
+        )))
+    }
+}
+
+impl Setting {
+    /// Resolves the path of a rhai script relative to the script directory
+    pub(crate) fn script_path(&self, script: &str) -> PathBuf {
+        let path = PathBuf::from(script);
+
+        if path.is_absolute() {
+            path
+        } else {
+            self.config.script_dir().join(path)
+        }
+    }
+
+    /// Converts target values into the values templates see, running any scripts
+    pub(crate) fn values(
+        &self,
+        table: HashMap<String, TargetValue>,
+        engine: &Engine,
+    ) -> Result<HashMap<String, ScriptValue>, CrateError> {
+        let mut map = HashMap::new();
+
+        for (k, v) in table {
+            match v {
+                TargetValue::Boolean(b) => {
+                    map.insert(k, ScriptValue::Boolean(b));
+                }
+
+                TargetValue::Integer(i) => {
+                    map.insert(k, ScriptValue::Integer(i));
+                }
+
+                TargetValue::Float(f) => {
+                    map.insert(k, ScriptValue::Float(f));
+                }
+
+                TargetValue::String(s) => {
+                    map.insert(k, ScriptValue::String(s));
+                }
+
+                TargetValue::Array(a) => {
+                    map.insert(k, ScriptValue::Array(a));
+                }
+
+                TargetValue::Table(t) => {
+                    map.insert(k, ScriptValue::Map(self.values(t, engine)?));
+                }
+
+                TargetValue::Script { script, value } => {
+                    map.extend(script::eval_rhai(self.script_path(&script), value, engine)?);
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Renders a template with a map of values, naming the setting as the owner of the output
+    pub(crate) fn render_template(
+        &self,
+        target: &Path,
+        map: HashMap<String, ScriptValue>,
+    ) -> Result<Option<Rendered>, CrateError> {
+        // templates whose condition does not hold are not written at all
+        Ok(
+            template::generate_config(target, map)?.map(|(header, contents)| Rendered {
+                template: target.to_owned(),
+                path: header.path().to_owned(),
+                header,
+                setting: self.id(),
+                contents,
+            }),
+        )
+    }
+
+    /// Renders the values the setting merges into each of its structured files
+    pub(crate) fn render_merges(&self, engine: &Engine) -> Result<Vec<Rendered>, CrateError> {
+        let mut outputs = Vec::new();
+
+        for (path, format, table) in &self.merges {
+            let values = self.values(table.to_owned(), engine)?;
+            let map = match serde_json::to_value(values) {
+                Ok(serde_json::Value::Object(map)) => map,
+                _ => serde_json::Map::new(),
+            };
+
+            outputs.push(Rendered {
+                template: self.path(),
+                path: path.to_owned(),
+                header: Header::merge(path.to_owned(), *format),
+                setting: self.id(),
+                contents: serde_json::Value::Object(map).to_string(),
+            });
+        }
+
+        Ok(outputs)
+    }
+
+    /// Templates every target of the setting on its own
+    pub fn render(&self, engine: &Engine) -> Result<Vec<Rendered>, CrateError> {
+        let mut outputs = Vec::new();
+
+        // go through each target for the current setting
+        for target in self.templates() {
+            // populate the map to template with using target values
+            let map = self.values(self.compose_map(&target), engine)?;
+            outputs.extend(self.render_template(&target, map)?);
+        }
+
+        // structured files get the values of their table merged into them
+        outputs.extend(self.render_merges(engine)?);
+
+        Ok(outputs)
+    }
+}
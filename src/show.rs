@@ -0,0 +1,162 @@
+use rconfigure::hook::Hook;
+use rconfigure::{profile, setting, template, Config, CrateError};
+use std::path::{Path, PathBuf};
+
+/// Shortens a path to be relative to a directory when it is inside it
+fn relative(path: &Path, dir: &Path) -> String {
+    path.strip_prefix(dir).unwrap_or(path).display().to_string()
+}
+
+/// Prints a titled list of lines, leaving it out when there is nothing in it
+fn section<I: IntoIterator<Item = String>>(title: &str, lines: I) {
+    let mut lines = lines.into_iter().peekable();
+
+    if lines.peek().is_some() {
+        println!("    {}:", title);
+
+        for line in lines {
+            println!("        {}", line);
+        }
+    }
+}
+
+fn hooks(title: &str, hooks: &[Hook]) {
+    section(title, hooks.iter().map(Hook::to_string));
+}
+
+/// Prints a profile along with its settings, the files they write and its hooks
+pub fn profile(config: &Config, path: &Path) -> Result<(), CrateError> {
+    let profile = profile::parse(config, path)?;
+    let mut targets = Vec::new();
+
+    for setting in profile.settings() {
+        for target in setting.templates() {
+            targets.push(relative(&target, &config.templates_dir()));
+        }
+
+        for merge in setting.merges() {
+            targets.push(merge.display().to_string());
+        }
+    }
+
+    targets.sort();
+    targets.dedup();
+
+    println!(
+        "{:?} ({})",
+        profile.name(),
+        relative(&profile.path(), config.root())
+    );
+
+    section(
+        "extends",
+        profile
+            .extends()
+            .iter()
+            .map(|path| relative(path, config.root())),
+    );
+    section(
+        "settings",
+        profile.settings().iter().map(|setting| {
+            format!(
+                "{:?} ({})",
+                setting.name(),
+                relative(&setting.path(), config.root())
+            )
+        }),
+    );
+    section("targets", targets);
+    hooks("hooks", profile.hooks());
+    hooks("unset hooks", profile.unset_hooks());
+
+    Ok(())
+}
+
+/// Prints a setting along with the templates it targets, the files it merges into and its hooks
+pub fn setting(config: &Config, path: &Path) -> Result<(), CrateError> {
+    let setting = setting::parse(config, path)?;
+
+    println!(
+        "{:?} ({})",
+        setting.name(),
+        relative(&setting.path(), config.root())
+    );
+
+    if let Some(base) = setting.extends() {
+        println!("    extends: {}", relative(base, config.root()));
+    }
+
+    if setting.priority() != 0 {
+        println!("    priority: {}", setting.priority());
+    }
+
+    section(
+        "targets",
+        setting
+            .templates()
+            .iter()
+            .map(|path| relative(path, &config.templates_dir())),
+    );
+    section(
+        "merges into",
+        setting
+            .merges()
+            .iter()
+            .map(|path| path.display().to_string()),
+    );
+    hooks("hooks", setting.hooks());
+    hooks("unset hooks", setting.unset_hooks());
+    hooks("disable hooks", setting.disable_hooks());
+
+    Ok(())
+}
+
+/// Prints a template along with where it is written
+pub fn template(config: &Config, path: &Path) -> Result<(), CrateError> {
+    let (header, _) = template::parse(path)?;
+
+    println!("{}", relative(path, &config.templates_dir()));
+    println!("    writes to: {}", header.path().display());
+
+    if header.block() {
+        println!("    as a managed block");
+    }
+
+    Ok(())
+}
+
+type Show = fn(&Config, &Path) -> Result<(), CrateError>;
+
+/// Prints every profile, setting and template a path given on the command line could refer to,
+/// either relative to their own directory or to the config directory
+pub fn item(config: &Config, item: &str) -> Result<(), CrateError> {
+    let item = Path::new(item);
+    let show: [(PathBuf, Show); 3] = [
+        (config.profiles_dir(), profile),
+        (config.settings_dir(), setting),
+        (config.templates_dir(), template),
+    ];
+
+    let mut found = false;
+
+    for (dir, show) in &show {
+        let path = if item.is_absolute() {
+            item.to_owned()
+        } else if config.root().join(item).starts_with(dir) {
+            config.root().join(item)
+        } else {
+            dir.join(item)
+        };
+
+        if path.starts_with(dir) && path.is_file() {
+            show(config, &path)?;
+            found = true;
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(CrateError::NoSuchItem(item.to_owned()))
+    }
+}
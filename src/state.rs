@@ -0,0 +1,177 @@
+use crate::config::Config;
+use crate::errors::CrateError;
+use crate::setting::{Rendered, Setting};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// What rconfigure wrote to a single output during the last apply and where it came from
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Output {
+    settings: Vec<PathBuf>,
+    template: PathBuf,
+    hash: String,
+    #[serde(default)]
+    block: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+impl Output {
+    /// Get the paths of every setting that contributed to the output
+    pub fn settings(&self) -> &[PathBuf] {
+        &self.settings
+    }
+
+    /// Get the path of the template that produced the output
+    pub fn template(&self) -> &Path {
+        &self.template
+    }
+
+    /// Get the hash of the contents that were written
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    /// Get whether rconfigure only manages blocks inside the output rather than the whole file
+    pub fn block(&self) -> bool {
+        self.block
+    }
+
+    /// Get the comment syntax the markers of a managed block start with
+    pub fn comment(&self) -> &str {
+        self.comment.as_deref().unwrap_or("#")
+    }
+}
+
+/// Record of every file written by the last apply
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct State {
+    profile: Option<PathBuf>,
+    applied: Option<DateTime<Local>>,
+    #[serde(default)]
+    outputs: BTreeMap<PathBuf, Output>,
+}
+
+/// How a managed output compares to what the last apply wrote
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputStatus {
+    InSync,
+    Modified,
+    Missing,
+    NeverWritten,
+}
+
+impl fmt::Display for OutputStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            OutputStatus::InSync => "in sync",
+            OutputStatus::Modified => "modified",
+            OutputStatus::Missing => "missing",
+            OutputStatus::NeverWritten => "never written",
+        })
+    }
+}
+
+/// Hashes file contents so they can be compared later
+pub fn hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl State {
+    /// Reads the state of the last apply, which is empty if nothing has been applied
+    pub fn load(config: &Config) -> Result<State, CrateError> {
+        let file = config.state_file();
+
+        match fs::read_to_string(&file) {
+            Ok(s) => Ok(toml::from_str(s.as_str())?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(State::default()),
+            Err(err) => Err(CrateError::ReadFailure(file, err)),
+        }
+    }
+
+    /// Creates an empty state for a profile that is being applied
+    pub fn new<P: AsRef<Path>>(profile: P) -> State {
+        State {
+            profile: Some(profile.as_ref().to_owned()),
+            applied: Some(Local::now()),
+            outputs: BTreeMap::new(),
+        }
+    }
+
+    /// Records a rendered output of some settings along with the full contents about to be
+    /// written, adding to what other settings already wrote to the same output
+    pub fn record(&mut self, rendered: &Rendered, settings: &[&Setting], contents: &[u8]) {
+        let mut paths: Vec<PathBuf> = self
+            .outputs
+            .remove(&rendered.path)
+            .map(|output| output.settings)
+            .unwrap_or_default();
+
+        for setting in settings {
+            if !paths.contains(&setting.path()) {
+                paths.push(setting.path());
+            }
+        }
+
+        let output = Output {
+            settings: paths,
+            template: rendered.template.to_owned(),
+            hash: hash(contents),
+            block: rendered.header.block(),
+            comment: Some(rendered.header.comment())
+                .filter(|_| rendered.header.block())
+                .map(str::to_owned),
+        };
+
+        self.outputs.insert(rendered.path.to_owned(), output);
+    }
+
+    /// Writes the state, replacing the one from the previous apply
+    pub fn save(&self, config: &Config) -> Result<(), CrateError> {
+        let file = config.state_file();
+        fs::write(&file, toml::to_string(self)?)
+            .map_err(|err| CrateError::WriteFailure(file, err))?;
+
+        Ok(())
+    }
+
+    /// Get the path of the profile that was applied
+    pub fn profile(&self) -> Option<&Path> {
+        self.profile.as_deref()
+    }
+
+    /// Get the time the profile was applied
+    pub fn applied(&self) -> Option<DateTime<Local>> {
+        self.applied
+    }
+
+    /// Get every recorded output
+    pub fn outputs(&self) -> &BTreeMap<PathBuf, Output> {
+        &self.outputs
+    }
+
+    /// Compares an output path on disk against what was recorded for it
+    pub fn status(&self, path: &Path) -> Result<OutputStatus, CrateError> {
+        let output = match self.outputs.get(path) {
+            Some(output) => output,
+            None => return Ok(OutputStatus::NeverWritten),
+        };
+
+        match fs::read(path) {
+            Ok(contents) if hash(&contents) == output.hash => Ok(OutputStatus::InSync),
+            Ok(_) => Ok(OutputStatus::Modified),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(OutputStatus::Missing),
+            Err(err) => Err(CrateError::ReadFailure(path.to_owned(), err)),
+        }
+    }
+}
@@ -1,26 +1,330 @@
-use far::{far, Errors};
+use crate::config::Config;
+use crate::errors::{CrateError, TemplateError};
+use crate::merge::Format;
+use crate::script::ScriptValue;
+use crate::walk;
+use minijinja::{filters, AutoEscape, Environment, UndefinedBehavior};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::{collections::hash_map::RandomState, collections::HashMap, fs, iter::FromIterator};
 
-/// Parses a template file and generates tries to generate the completed config file from it
-pub fn generate_config<P: AsRef<Path>>(
-    path: P,
-    map: HashMap<String, String>,
-) -> Result<(PathBuf, String), Errors> {
-    let data = fs::read_to_string(path.as_ref()).unwrap();
+/// The encoding a rendered template is written in
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-8-bom")]
+    Utf8Bom,
+    /// Little endian with a byte order mark, the way Windows tools write UTF-16
+    #[serde(rename = "utf-16")]
+    Utf16,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+}
+
+impl Encoding {
+    /// Decodes bytes written in the encoding, replacing anything invalid
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let s = match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16 | Encoding::Utf16Le | Encoding::Utf16Be => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Encoding::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                        _ => u16::from_le_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+
+                String::from_utf16_lossy(&units)
+            }
+        };
+
+        // the byte order mark is part of the encoding, not the contents
+        match s.strip_prefix('\u{feff}') {
+            Some(s) => s.to_owned(),
+            None => s,
+        }
+    }
+}
+
+/// The line endings a rendered template is written with
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Newline {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_comment() -> String {
+    String::from("#")
+}
+
+/// Metadata of a template, either a lone path on its first line or TOML front matter between
+/// two `+++` lines
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Header {
+    path: PathBuf,
+    mode: Option<u32>,
+    #[serde(default)]
+    create_dirs: bool,
+    #[serde(default)]
+    encoding: Encoding,
+    #[serde(default)]
+    newline: Newline,
+    #[serde(default = "default_true")]
+    final_newline: bool,
+    #[serde(default)]
+    variables: HashMap<String, ScriptValue>,
+    only_if: Option<String>,
+    #[serde(default)]
+    block: bool,
+    #[serde(default = "default_comment")]
+    comment: String,
+    #[serde(skip)]
+    merge: Option<Format>,
+}
+
+impl Header {
+    /// Creates the header of a template with only a path on its first line
+    fn from_path(path: PathBuf) -> Self {
+        Header {
+            path,
+            mode: None,
+            create_dirs: false,
+            encoding: Encoding::default(),
+            newline: Newline::default(),
+            final_newline: true,
+            variables: HashMap::new(),
+            only_if: None,
+            block: false,
+            comment: default_comment(),
+            merge: None,
+        }
+    }
+
+    /// Creates the header of a structured file that a setting merges values into
+    pub(crate) fn merge(path: PathBuf, format: Format) -> Self {
+        Header {
+            merge: Some(format),
+            ..Header::from_path(path)
+        }
+    }
+
+    /// Get the path the rendered template is written to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the permissions the output is given
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+
+    /// Get whether missing parent directories of the output are created
+    pub fn create_dirs(&self) -> bool {
+        self.create_dirs
+    }
+
+    /// Get the encoding of the output
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Get the line endings of the output
+    pub fn newline(&self) -> Newline {
+        self.newline
+    }
+
+    /// Get whether the output ends with a newline
+    pub fn final_newline(&self) -> bool {
+        self.final_newline
+    }
+
+    /// Get the variables declared by the template along with their default values
+    pub fn variables(&self) -> &HashMap<String, ScriptValue> {
+        &self.variables
+    }
+
+    /// Get the condition that has to hold for the template to be written at all
+    pub fn only_if(&self) -> Option<&str> {
+        self.only_if.as_deref()
+    }
+
+    /// Get whether the output is a managed block inside a file instead of the whole file
+    pub fn block(&self) -> bool {
+        self.block
+    }
+
+    /// Get the comment syntax the markers of a managed block start with
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
+    /// Get the format of the output if values are merged into it rather than templated
+    pub fn merge_format(&self) -> Option<Format> {
+        self.merge
+    }
+
+    /// Get the line ending as a string
+    pub fn newline_str(&self) -> &'static str {
+        match self.newline {
+            Newline::Lf => "\n",
+            Newline::Crlf => "\r\n",
+        }
+    }
+
+    /// Encodes rendered contents into the bytes that get written
+    pub fn encode(&self, contents: &str) -> Vec<u8> {
+        // only the encodings that don't name a byte order start with a byte order mark
+        match self.encoding {
+            Encoding::Utf8 => contents.as_bytes().to_vec(),
+            Encoding::Utf8Bom => [&[0xef, 0xbb, 0xbf], contents.as_bytes()].concat(),
+            Encoding::Utf16 => std::iter::once('\u{feff}' as u16)
+                .chain(contents.encode_utf16())
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+            Encoding::Utf16Le => contents.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Encoding::Utf16Be => contents.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+
+    /// Decodes the bytes of an output on disk, replacing anything invalid
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        self.encoding.decode(bytes)
+    }
+}
+
+/// Wraps a value in double quotes, escaping any backslashes and quotes inside it
+fn quote(value: String) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Creates the environment templates are rendered in
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+
+    // printing a value no setting provides is an error, but conditionals treat it as false
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env.set_keep_trailing_newline(true);
+
+    // config files are written as is, so values are never escaped based on the file extension
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+
+    // keep lines with only block tags on them from leaving blank lines in the output
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+
+    env.add_filter("quote", quote);
+    env.add_filter("json", filters::tojson);
+
+    env
+}
+
+/// Expands a leading `~` and any `$VAR` or `${VAR}` environment variables in an output path
+/// declared in `file`, with `$$` standing for a literal `$`
+pub(crate) fn expand_vars(file: &Path, path: &str) -> Result<PathBuf, CrateError> {
+    let undefined = |variable: &str| TemplateError::UndefinedVariable {
+        file: file.to_owned(),
+        variable: variable.to_owned(),
+    };
+
+    let mut expanded = String::new();
+    let mut rest = path;
+
+    if rest == "~" || rest.starts_with("~/") {
+        let home = dirs::home_dir().ok_or_else(|| undefined("HOME"))?;
+        expanded.push_str(&home.to_string_lossy());
+        rest = &rest[1..];
+    }
+
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => return Err(undefined(braced).into()),
+            }
+        } else if rest.starts_with('$') {
+            expanded.push('$');
+            rest = &rest[1..];
+            continue;
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (&rest[..end], end)
+        };
+
+        // a lone `$` is kept as is
+        if name.is_empty() && len == 0 {
+            expanded.push('$');
+            continue;
+        }
+
+        let value = std::env::var_os(name).ok_or_else(|| undefined(name))?;
+        expanded.push_str(&value.to_string_lossy());
+        rest = &rest[len..];
+    }
+
+    expanded.push_str(rest);
+
+    Ok(PathBuf::from(expanded))
+}
+
+/// Lists the path of every template in the templates directory
+pub fn list(config: &Config) -> Result<Vec<PathBuf>, CrateError> {
+    walk::list(&config.templates_dir())
+}
+
+/// Reads a template file and splits it into its header and the template data
+pub fn parse<P: AsRef<Path>>(path: P) -> Result<(Header, String), CrateError> {
+    let path = path.as_ref();
+    let data = fs::read_to_string(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => TemplateError::MissingTemplate(path.to_owned()),
+        _ => TemplateError::ErrorReadingTemplate(path.to_owned(), err),
+    })?;
+
     let mut lines = data.lines();
     let mut template = String::new();
 
-    // the first line of a template is always a path and must be included
-    let header = match lines.next().map(|s| Path::new(s)) {
-        Some(path) => path,
-        None => {
-            println!(
-                "failed to parse template {:?}, missing header",
-                path.as_ref()
-            );
-            std::process::exit(1);
+    // the first line of a template is either a path or the start of its front matter
+    let header = match lines.next().map(str::trim) {
+        Some("+++") => {
+            let mut front_matter = String::new();
+
+            loop {
+                match lines.next() {
+                    Some(line) if line.trim() == "+++" => break,
+                    Some(line) => {
+                        front_matter.push_str(line);
+                        front_matter.push('\n');
+                    }
+                    None => {
+                        return Err(TemplateError::UnterminatedFrontMatter(path.to_owned()).into())
+                    }
+                }
+            }
+
+            toml::from_str(&front_matter)
+                .map_err(|err| TemplateError::ErrorParsingFrontMatter(path.to_owned(), err))?
         }
+
+        Some(header) if !header.is_empty() => Header::from_path(PathBuf::from(header)),
+        _ => return Err(TemplateError::MissingTemplateHeader(path.to_owned()).into()),
     };
 
     // skip all the empty lines following the header
@@ -37,23 +341,101 @@ pub fn generate_config<P: AsRef<Path>>(
     // collect all of the actual template data
     if let Some(first_line) = first_line {
         template.push_str(first_line);
-        template.push_str("\n");
+        template.push('\n');
 
         for line in lines {
-            let line = String::from(line);
+            template.push_str(line);
+            template.push('\n');
+        }
+    }
+
+    Ok((header, template))
+}
+
+/// Parses a template file and lists the names of every value its body, output path and
+/// condition read
+pub fn variables<P: AsRef<Path>>(path: P) -> Result<(Header, HashSet<String>), CrateError> {
+    let path = path.as_ref();
+    let (header, template) = parse(path)?;
+    let env = environment();
+
+    let mut variables = env
+        .template_from_named_str(&path.to_string_lossy(), &template)
+        .map_err(|err| TemplateError::RenderFailure(path.to_owned(), err))?
+        .undeclared_variables(false);
+
+    let output = header.path.to_string_lossy();
+    variables.extend(
+        env.template_from_str(&output)
+            .map_err(|err| TemplateError::PathExpansionFailure(path.to_owned(), err))?
+            .undeclared_variables(false),
+    );
+
+    if let Some(condition) = header.only_if() {
+        variables.extend(
+            env.compile_expression(condition)
+                .map_err(|err| TemplateError::RenderFailure(path.to_owned(), err))?
+                .undeclared_variables(false),
+        );
+    }
+
+    // functions like `range` are always there
+    for (name, _) in env.globals() {
+        variables.remove(name);
+    }
+
+    Ok((header, variables))
+}
 
-            template.push_str(line.as_str());
-            template.push_str("\n");
+/// Parses a template file and generates tries to generate the completed config file from it,
+/// giving `None` when the template's `only_if` condition does not hold
+pub fn generate_config<P: AsRef<Path>>(
+    path: P,
+    mut map: HashMap<String, ScriptValue>,
+) -> Result<Option<(Header, String)>, CrateError> {
+    let path = path.as_ref();
+    let (mut header, template) = parse(path)?;
+    let env = environment();
+
+    // declared variables fall back to their defaults when no setting provides them
+    for (k, v) in header.variables() {
+        map.entry(k.to_owned()).or_insert_with(|| v.to_owned());
+    }
+
+    if let Some(condition) = header.only_if() {
+        let value = env
+            .compile_expression(condition)
+            .and_then(|expr| expr.eval(&map))
+            .map_err(|err| TemplateError::RenderFailure(path.to_owned(), err))?;
+
+        if !value.is_true() {
+            return Ok(None);
         }
     }
 
-    Ok((
-        header.to_owned(),
-        far(
-            template,
-            &HashMap::<&str, &str, RandomState>::from_iter(
-                map.iter().map(|(k, v)| (k.as_str(), v.as_str())),
-            ),
-        )?,
-    ))
+    // `~` and `$VAR` are expanded before setting values go in so the values stay as they are
+    let output = expand_vars(path, &header.path.to_string_lossy())?;
+    let output = env
+        .render_str(&output.to_string_lossy(), &map)
+        .map_err(|err| TemplateError::PathExpansionFailure(path.to_owned(), err))?;
+    header.path = PathBuf::from(output);
+
+    let mut contents = env
+        .render_named_str(&path.to_string_lossy(), &template, map)
+        .map_err(|err| TemplateError::RenderFailure(path.to_owned(), err))?;
+
+    // the file ends in exactly one newline, or none at all
+    while contents.ends_with('\n') {
+        contents.pop();
+    }
+
+    if header.final_newline() && !contents.is_empty() {
+        contents.push('\n');
+    }
+
+    if header.newline() == Newline::Crlf {
+        contents = contents.replace('\n', "\r\n");
+    }
+
+    Ok(Some((header, contents)))
 }
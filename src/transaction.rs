@@ -0,0 +1,153 @@
+use crate::errors::CrateError;
+use crate::template::Encoding;
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A file that is about to be written
+#[derive(Clone, Debug)]
+pub struct FileWrite {
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+    /// Permissions to give the file instead of keeping the existing ones
+    pub mode: Option<u32>,
+    /// Whether missing parent directories get created
+    pub create_dirs: bool,
+    /// The encoding of the contents, to read them back as text
+    pub encoding: Encoding,
+}
+
+impl FileWrite {
+    /// Creates a write of plain contents that keeps the existing permissions
+    pub fn new<P: Into<PathBuf>>(path: P, contents: Vec<u8>) -> Self {
+        FileWrite {
+            path: path.into(),
+            contents,
+            mode: None,
+            create_dirs: false,
+            encoding: Encoding::default(),
+        }
+    }
+}
+
+/// Sets the unix permission bits of a file
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Writes a file by writing a temporary file next to it and renaming it into place
+fn write_atomic(path: &Path, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".rconfigure-tmp");
+    let tmp = path.with_file_name(name);
+
+    fs::write(&tmp, contents)?;
+
+    // keep the permissions of the file being replaced unless others were asked for
+    if let Some(mode) = mode {
+        set_mode(&tmp, mode)?;
+    } else if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&tmp, metadata.permissions())?;
+    }
+
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Writes every output or, if any write fails, restores the files that were already written
+pub fn write_all(outputs: &[FileWrite]) -> Result<(), CrateError> {
+    commit(outputs, &[])
+}
+
+/// Writes every output and removes every file in `removals` or, if anything fails, puts back the
+/// files that were already changed
+pub fn commit(outputs: &[FileWrite], removals: &[PathBuf]) -> Result<(), CrateError> {
+    // previous contents of every file changed so far, `None` if it did not exist
+    let mut written: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+
+    for output in outputs {
+        // write through symlinks instead of replacing them
+        let path = fs::canonicalize(&output.path).unwrap_or_else(|_| output.path.to_owned());
+
+        if output.create_dirs {
+            if let Some(parent) = path.parent() {
+                if let Err(err) = fs::create_dir_all(parent) {
+                    return Err(rollback(written, CrateError::WriteFailure(path, err)));
+                }
+            }
+        }
+
+        let previous = match fs::read(&path) {
+            Ok(previous) => Some(previous),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(rollback(written, CrateError::WriteFailure(path, err)));
+            }
+        };
+
+        if let Err(err) = write_atomic(&path, &output.contents, output.mode) {
+            return Err(rollback(written, CrateError::WriteFailure(path, err)));
+        }
+
+        written.push((path, previous));
+    }
+
+    for path in removals {
+        let previous = match fs::read(path) {
+            Ok(previous) => previous,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(rollback(
+                    written,
+                    CrateError::WriteFailure(path.to_owned(), err),
+                ));
+            }
+        };
+
+        if let Err(err) = fs::remove_file(path) {
+            return Err(rollback(
+                written,
+                CrateError::WriteFailure(path.to_owned(), err),
+            ));
+        }
+
+        written.push((path.to_owned(), Some(previous)));
+    }
+
+    Ok(())
+}
+
+/// Puts back the previous contents of written files newest first after a write failed, giving
+/// the error to report for it
+fn rollback(written: Vec<(PathBuf, Option<Vec<u8>>)>, err: CrateError) -> CrateError {
+    let mut failures = Vec::new();
+
+    for (path, previous) in written.into_iter().rev() {
+        let result = match previous {
+            Some(previous) => write_atomic(&path, &previous, None),
+            None => fs::remove_file(&path),
+        };
+
+        if let Err(err) = result {
+            failures.push((path, err));
+        }
+    }
+
+    if failures.is_empty() {
+        err
+    } else {
+        CrateError::RollbackFailure(Box::new(err), failures)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::errors::CrateError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Resolves a path against the current directory unless it is already absolute
+pub fn absolute<P: AsRef<Path>>(path: P) -> Result<PathBuf, CrateError> {
+    Ok(if path.as_ref().is_absolute() {
+        path.as_ref().to_owned()
+    } else {
+        std::env::current_dir()
+            .map_err(|err| CrateError::ReadFailure(PathBuf::from("."), err))?
+            .join(path)
+    })
+}
+
+/// Recursively collects every file inside a directory
+pub fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), CrateError> {
+    let entries = fs::read_dir(dir).map_err(|err| CrateError::ReadFailure(dir.to_owned(), err))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| CrateError::ReadFailure(dir.to_owned(), err))?
+            .path();
+
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists every file inside a config directory that isn't hidden, in order
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>, CrateError> {
+    let mut files = Vec::new();
+
+    if dir.is_dir() {
+        walk(dir, &mut files)?;
+    }
+
+    files.retain(|file| {
+        !file
+            .strip_prefix(dir)
+            .unwrap_or(file)
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+    });
+    files.sort();
+
+    Ok(files)
+}